@@ -0,0 +1,86 @@
+use std::{fs::File, io::Write};
+
+use automated_chessboard::chess::{
+    chess::Chess,
+    selfplay::{read_openings, Engine, Match},
+};
+
+const USAGE: &str = "Usage: selfplay <first engine> <second engine> [--games <number>] \
+    [--openings <file>] [--pgn <file>]\n\
+    An engine is written as its evaluation and budget, for example positional:depth=4, \
+    material:nodes=20000 or positional:time=200";
+
+/// Plays a match between two configurations of the chess computer without the simulation, and
+/// prints the wins, draws and losses of the first engine and the estimated Elo difference.
+/// The openings are read from a file with a FEN or EPD on each line, and every opening is played
+/// with both colors. Without openings, all games start from the initial position. With `--pgn`
+/// every game is written to the given file.
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut engine = || {
+        args.next()
+            .unwrap_or_else(|| exit_with(USAGE.to_owned()))
+            .parse::<Engine>()
+            .unwrap_or_else(|error| exit_with(error))
+    };
+    let first = engine();
+    let second = engine();
+
+    let mut games = 20;
+    let mut openings = vec![Chess::default()];
+    let mut pgn = None;
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with(USAGE.to_owned()));
+        match flag.as_str() {
+            "--games" => {
+                games = value
+                    .parse()
+                    .unwrap_or_else(|_| exit_with(format!("Not a number of games: {value}")))
+            }
+            "--openings" => {
+                let text = std::fs::read_to_string(&value)
+                    .unwrap_or_else(|error| exit_with(format!("Could not read {value}: {error}")));
+                openings = read_openings(&text)
+                    .unwrap_or_else(|error| exit_with(format!("Could not read {value}: {error}")));
+                if openings.is_empty() {
+                    exit_with(format!("There are no openings in {value}"));
+                }
+            }
+            "--pgn" => {
+                let file = File::create(&value).unwrap_or_else(|error| {
+                    exit_with(format!("Could not create {value}: {error}"))
+                });
+                pgn = Some(file);
+            }
+            _ => exit_with(USAGE.to_owned()),
+        }
+    }
+
+    let game_match = Match {
+        first,
+        second,
+        openings,
+        games,
+    };
+    println!("{first} against {second}, {games} games");
+    let results = game_match.play(|record, results| {
+        println!(
+            "game {}: {} - {} {} ({results})",
+            results.games(),
+            record.white,
+            record.black,
+            record.result()
+        );
+        if let Some(file) = &mut pgn {
+            writeln!(file, "{}", record.to_pgn())
+                .unwrap_or_else(|error| exit_with(format!("Could not write the PGN: {error}")));
+        }
+    });
+    println!("{first} against {second}: {results}");
+}
+
+/// Prints why the match can not be played and stops.
+fn exit_with(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
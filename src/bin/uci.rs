@@ -0,0 +1,5 @@
+/// Runs the chess computer as a UCI engine, so it can play in chess user interfaces and against
+/// other engines. Bevy and the simulation are not started.
+fn main() {
+    automated_chessboard::chess::uci::run();
+}
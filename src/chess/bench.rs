@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+use crate::chess::{
+    chess::Chess,
+    computer::{minimax, search, Budget, Limit},
+    evaluation::Positional,
+    fen::START_FEN,
+    ordering::MoveOrder,
+    score::Score,
+    transposition::TranspositionTable,
+};
+
+/// The positions that are measured: the start position, and a middlegame position with
+/// castling, en passant, promotions and pins.
+const POSITIONS: [(&str, &str); 2] = [
+    ("start", START_FEN),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+];
+
+/// Measures how fast moves are generated and searched, and prints the results. Move generation
+/// is measured with [`Chess::perft`], the search with [`minimax`] at increasing depths, which
+/// share a [`TranspositionTable`] and a [`MoveOrder`] like the searches of the computer player
+/// do, and with [`search`] for a second. Both are measured with and without the table. Build
+/// with `--release` for meaningful numbers.
+pub fn run() {
+    for (name, fen) in POSITIONS {
+        let mut chess = Chess::from_fen(fen).unwrap();
+
+        let start = Instant::now();
+        let nodes = chess.perft(4);
+        let elapsed = start.elapsed();
+        println!(
+            "perft 4 of {name}: {nodes} nodes in {elapsed:.2?} ({:.0} nodes/s)",
+            per_second(nodes, elapsed)
+        );
+
+        // A table with a single slot forgets almost everything, which shows how many nodes the
+        // table saves, and how much deeper it lets the computer player search in the same time.
+        for (table_name, size) in [("table", TranspositionTable::DEFAULT_SIZE), ("no table", 1)] {
+            let mut table = TranspositionTable::new(size);
+            let mut order = MoveOrder::default();
+            for depth in 3..=5 {
+                let start = Instant::now();
+                let mut limit = Limit::unlimited();
+                let best = minimax::<Positional>(
+                    &mut chess,
+                    &mut table,
+                    &mut order,
+                    &mut limit,
+                    depth,
+                    -Score::INFINITY,
+                    Score::INFINITY,
+                );
+                println!(
+                    "search of {name} at depth {depth} with {table_name}: {:?}, {} nodes in {:.2?}",
+                    best.m,
+                    limit.nodes(),
+                    start.elapsed()
+                );
+            }
+
+            let mut table = TranspositionTable::new(size);
+            let best = search::<Positional>(
+                &mut chess,
+                &mut table,
+                Limit::new(Budget::Time(Duration::from_secs(1))),
+            );
+            println!(
+                "search of {name} for 1s with {table_name}: {:?} at depth {}",
+                best.m, best.depth
+            );
+        }
+    }
+}
+
+fn per_second(nodes: u64, elapsed: Duration) -> f64 {
+    nodes as f64 / elapsed.as_secs_f64()
+}
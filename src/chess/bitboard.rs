@@ -0,0 +1,212 @@
+use crate::chess::{chess::Color, pos::Pos};
+
+/// A set of squares on the board, with one bit for each square. Bit `8 * y + x` stands for the
+/// square at `Pos::new(x, y)`, so a1 is the lowest bit and h8 the highest.
+pub type Bitboard = u64;
+
+/// Squares a knight on each square can jump to.
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = step_table(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+
+/// Squares a king on each square can step to.
+pub const KING_ATTACKS: [Bitboard; 64] = step_table(&[
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+]);
+
+/// Squares a pawn on each square attacks, for white on index 0 and black on index 1 like
+/// [`Color::king_index`]. White pawns move towards the higher rows.
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    step_table(&[(-1, 1), (1, 1)]),
+    step_table(&[(-1, -1), (1, -1)]),
+];
+
+/// The directions a rook or bishop slides in, as steps of x and y. The first four directions
+/// move towards higher squares, the last four towards lower squares.
+const DIRECTIONS: [(isize, isize); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (-1, 1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (1, -1),
+];
+
+/// For each direction of [`DIRECTIONS`] and each square, all squares from that square up to the
+/// edge of the board.
+const RAYS: [[Bitboard; 64]; 8] = ray_table();
+
+/// Returns the bitboard with only the bit of the given position set. The position must be on
+/// the board.
+pub fn bit(pos: Pos) -> Bitboard {
+    1 << square(pos)
+}
+
+/// Returns the index of the bit of a position on the board.
+pub fn square(pos: Pos) -> usize {
+    (pos.y() * 8 + pos.x()) as usize
+}
+
+/// Returns the position of the bit with the given index.
+pub fn pos(square: u32) -> Pos {
+    Pos::new((square % 8) as isize, (square / 8) as isize)
+}
+
+/// Returns an iterator over the positions of all squares in the bitboard, from a1 up to h8.
+pub fn positions(mut bitboard: Bitboard) -> impl Iterator<Item = Pos> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            None
+        } else {
+            let square = bitboard.trailing_zeros();
+            bitboard &= bitboard - 1;
+            Some(pos(square))
+        }
+    })
+}
+
+/// Returns the squares a pawn of the given color on the given square attacks.
+pub fn pawn_attacks(color: Color, pos: Pos) -> Bitboard {
+    PAWN_ATTACKS[color.king_index()][square(pos)]
+}
+
+/// Returns the squares a rook on the given square can move to, given all occupied squares. The
+/// first occupied square in each direction is included, whichever color the piece on it has.
+pub fn rook_attacks(pos: Pos, occupied: Bitboard) -> Bitboard {
+    slide(pos, occupied, &[0, 2, 4, 6])
+}
+
+/// Returns the squares a bishop on the given square can move to, given all occupied squares,
+/// like [`rook_attacks`].
+pub fn bishop_attacks(pos: Pos, occupied: Bitboard) -> Bitboard {
+    slide(pos, occupied, &[1, 3, 5, 7])
+}
+
+/// Combines the rays in the given directions from a square. Each ray is cut off after the first
+/// occupied square on it, which is the lowest one for rays towards higher squares and the
+/// highest one for the others.
+fn slide(pos: Pos, occupied: Bitboard, directions: &[usize]) -> Bitboard {
+    let square = square(pos);
+    directions
+        .iter()
+        .map(|direction| {
+            let ray = RAYS[*direction][square];
+            let blockers = ray & occupied;
+            if blockers == 0 {
+                ray
+            } else {
+                let blocker = if *direction < 4 {
+                    blockers.trailing_zeros()
+                } else {
+                    63 - blockers.leading_zeros()
+                };
+                ray ^ RAYS[*direction][blocker as usize]
+            }
+        })
+        .fold(0, |attacks, ray| attacks | ray)
+}
+
+/// Builds a table of the squares reached from each square with one of the given steps.
+const fn step_table(steps: &[(isize, isize)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let (x, y) = ((square % 8) as isize, (square / 8) as isize);
+        let mut step = 0;
+        while step < steps.len() {
+            let (to_x, to_y) = (x + steps[step].0, y + steps[step].1);
+            if 0 <= to_x && to_x < 8 && 0 <= to_y && to_y < 8 {
+                table[square] |= 1 << (to_y * 8 + to_x);
+            }
+            step += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+/// Builds the table of [`RAYS`].
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let (dx, dy) = DIRECTIONS[direction];
+        let mut square = 0;
+        while square < 64 {
+            let (mut x, mut y) = ((square % 8) as isize + dx, (square / 8) as isize + dy);
+            while 0 <= x && x < 8 && 0 <= y && y < 8 {
+                table[direction][square] |= 1 << (y * 8 + x);
+                x += dx;
+                y += dy;
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{bitboard::*, chess::Color, pos::Pos};
+
+    #[test]
+    fn test_positions() {
+        let bitboard = bit(Pos::new(0, 0)) | bit(Pos::new(4, 3)) | bit(Pos::new(7, 7));
+        let positions: Vec<Pos> = positions(bitboard).collect();
+        assert_eq!(
+            positions,
+            vec![Pos::new(0, 0), Pos::new(4, 3), Pos::new(7, 7)]
+        );
+    }
+
+    #[test]
+    fn test_step_attacks() {
+        // A knight in the corner only has two squares to jump to.
+        assert_eq!(
+            KNIGHT_ATTACKS[square(Pos::new(0, 0))],
+            bit(Pos::new(1, 2)) | bit(Pos::new(2, 1))
+        );
+        assert_eq!(KING_ATTACKS[square(Pos::new(4, 4))].count_ones(), 8);
+        assert_eq!(
+            pawn_attacks(Color::White, Pos::new(0, 1)),
+            bit(Pos::new(1, 2))
+        );
+        assert_eq!(
+            pawn_attacks(Color::Black, Pos::new(4, 6)),
+            bit(Pos::new(3, 5)) | bit(Pos::new(5, 5))
+        );
+    }
+
+    #[test]
+    fn test_sliding_attacks() {
+        assert_eq!(rook_attacks(Pos::new(0, 0), 0).count_ones(), 14);
+        assert_eq!(bishop_attacks(Pos::new(3, 3), 0).count_ones(), 13);
+
+        // A rook on d4 is stopped by the pieces on d6 and b4, which it can capture.
+        let occupied = bit(Pos::new(3, 5)) | bit(Pos::new(1, 3));
+        let attacks = rook_attacks(Pos::new(3, 3), occupied);
+        assert!(attacks & bit(Pos::new(3, 5)) != 0);
+        assert!(attacks & bit(Pos::new(3, 6)) == 0);
+        assert!(attacks & bit(Pos::new(1, 3)) != 0);
+        assert!(attacks & bit(Pos::new(0, 3)) == 0);
+        assert_eq!(attacks.count_ones(), 2 + 2 + 4 + 3);
+    }
+}
@@ -1,41 +1,135 @@
-use crate::chess::pos::{Pos, Shift};
+use crate::chess::{
+    bitboard::{self, Bitboard, KING_ATTACKS, KNIGHT_ATTACKS},
+    pos::{Pos, Shift},
+    score::Score,
+    variant::Rules,
+    zobrist,
+};
 use std::borrow::Borrow;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::{Index, IndexMut, Not};
+use std::ops::{Index, Not};
 
 /// When a piece has been captured it will move to the sidelines,
 /// which are 2 colums named the Graveyard.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Each row of the graveyard lines up with a row next to the board, from -1 up to and including 8.
+/// The column next to the board comes first, the outer column holds the captured pieces that do
+/// not fit in it, so all pieces of a player fit in their graveyard.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Graveyard {
-    pub graveyard: [[Option<Piece>; 1]; 8],
+    pub graveyard: [[Option<Piece>; 2]; 10],
 }
 
-impl Graveyard {
-    pub fn default() -> Self {
-        let graveyard = [
-            [None],
-            [None],
-            [None],
-            [None],
-            [None],
-            [None],
-            [None],
-            [None],
-        ];
-        Graveyard { graveyard }
+/// Keeps track of the sides a player is still allowed to castle to. A right is lost as soon as
+/// the king or the rook on that side has moved, or when that rook has been captured.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CastlingRights {
+    pub king_side: bool,
+    pub queen_side: bool,
+}
+
+impl Default for CastlingRights {
+    fn default() -> Self {
+        CastlingRights {
+            king_side: true,
+            queen_side: true,
+        }
     }
 }
 
+/// The two moves of a castling: the king moving to the g or c column, and the rook moving to the
+/// column next to it on the inside. In Chess960 the king or rook may already stand on its
+/// destination, or stand on the destination of the other.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Castling {
+    pub king: Move,
+    pub rook: Move,
+}
+
 /// Keeps track of the state of the game, the location of all the pieces(boardstate) is kept in board. Whose turn it is in turn,
 /// the location of both kings in kings. And the state of the graveyards in graveyards.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+///
+/// The pieces on the board are kept twice: in board, which answers what stands on a square, and
+/// in bitboards, which answer where the pieces of a kind stand and are used to generate moves.
+/// Both are changed together by [`set`], so pieces can only be placed through it.
+///
+/// [`set`]: #method.set
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Chess {
-    pub board: [[Option<Piece>; 8]; 8],
+    board: [[Option<Piece>; 8]; 8],
+    /// The squares of all pieces of each color, stored on the same indices as `kings`.
+    colors: [Bitboard; 2],
+    /// The squares of all pieces of each kind, of both colors, stored on [`Kind::index`].
+    kinds: [Bitboard; 6],
+    /// The Zobrist key of the pieces on the board, kept up to date by [`set`].
+    ///
+    /// [`set`]: #method.set
+    pieces_key: u64,
     pub turn: Color,
     /// Keeps track of the current positions of both kings. White's king's position is stored on
-    /// index 0 and black's on 1.
+    /// index 0 and black's on 1. In a variant without a royal king, see [`Variant::royal_king`],
+    /// a king can be captured, so the positions are not used.
+    ///
+    /// [`Variant::royal_king`]: crate::chess::variant::Variant::royal_king
     pub kings: [Pos; 2],
     pub graveyards: [Graveyard; 2],
+    /// Castling rights of both players, stored on the same indices as `kings`.
+    pub castling_rights: [CastlingRights; 2],
+    /// The columns the rooks a player may castle with started on, the king side rook first, for
+    /// each player on the same indices as `kings`. In a position set up from a FEN, the players may
+    /// castle with rooks on different columns.
+    pub castling_columns: [[isize; 2]; 2],
+    /// Whether this is a game of Chess960, in which castling is written as the king moving onto
+    /// its own rook.
+    pub chess960: bool,
+    /// The variant of chess the game is played by.
+    pub rules: Rules,
+    /// The number of checks each player has given, stored on the same indices as `kings`. Only
+    /// counted when the variant asks for it, see [`Variant::counts_checks`].
+    ///
+    /// [`Variant::counts_checks`]: crate::chess::variant::Variant::counts_checks
+    pub checks: [u8; 2],
+    /// The square a pawn skipped over with a double step in the previous move. An enemy pawn
+    /// may capture that pawn en passant by moving to this square.
+    pub en_passant: Option<Pos>,
+    /// The number of moves since the last capture or pawn move, counting the moves of both
+    /// players. The game is drawn by the fifty-move rule once this reaches 100.
+    pub halfmove_clock: u16,
+    /// The number of the current move, starting at 1 and increased after every move of black.
+    pub fullmove_number: u16,
+    /// Every move performed in this game, with all that is needed to [`undo`] it. Also used to
+    /// detect repetitions.
+    ///
+    /// [`undo`]: #method.undo
+    pub history: Vec<Undo>,
+}
+
+/// A piece that has been captured: the position it was captured on, and the position in the
+/// graveyard it was moved to. That position is `None` when the graveyard was full.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Capture {
+    pub piece: Piece,
+    pub pos: Pos,
+    pub slot: Option<Pos>,
+}
+
+/// Everything that [`Chess::perform`] changes besides the moved piece, so that
+/// [`Chess::undo`] can restore the position from before the move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Undo {
+    pub m: Move,
+    /// The piece that moved, which is still a pawn for a promotion.
+    pub piece: Piece,
+    pub capture: Option<Capture>,
+    /// The moves of the king and the rook when castling.
+    pub castling: Option<Castling>,
+    /// The moves that swapped a promoted pawn for a captured piece, see [`Chess::promotion_swap`].
+    pub promotion_swap: Option<(Move, Move)>,
+    pub castling_rights: [CastlingRights; 2],
+    pub en_passant: Option<Pos>,
+    pub halfmove_clock: u16,
+    pub checks: [u8; 2],
+    /// The [`Chess::position_key`] of the position before the move.
+    pub key: u64,
 }
 
 impl Chess {
@@ -91,17 +185,152 @@ impl Chess {
 
         let kings = [Pos::new(4, 0), Pos::new(4, 7)];
         let graveyards = [Graveyard::default(), Graveyard::default()];
+        let castling_rights = [CastlingRights::default(), CastlingRights::default()];
 
-        Chess {
-            board,
+        let mut chess = Chess {
+            board: [[None; 8]; 8],
+            colors: [0; 2],
+            kinds: [0; 6],
+            pieces_key: 0,
             turn,
             kings,
             graveyards,
+            castling_rights,
+            castling_columns: [[7, 0]; 2],
+            chess960: false,
+            rules: Rules::Standard,
+            checks: [0; 2],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: Vec::new(),
+        };
+        for pos in Self::board_positions() {
+            chess.set(pos, board[pos.y() as usize][pos.x() as usize]);
+        }
+        chess
+    }
+
+    /// Returns the start position of Chess960 with the given index from 0 up to and including 959,
+    /// numbered like in the standard scheme. The back rows are filled from the index: the light
+    /// and dark squared bishops, the queen, the pair of knights and finally the rooks with the
+    /// king between them. Index 518 is the start position of classical chess.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the index is 960 or higher.
+    pub fn chess960(index: u16) -> Self {
+        assert!(index < 960, "there is no Chess960 start position {index}");
+        // The squares of the knights among the five squares left after the bishops and queen.
+        const KNIGHTS: [(usize, usize); 10] = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ];
+        let mut row: [Option<Kind>; 8] = [None; 8];
+        let index = index as usize;
+        row[2 * (index % 4) + 1] = Some(Kind::Bishop);
+        row[2 * (index / 4 % 4)] = Some(Kind::Bishop);
+        let empty = |row: &[Option<Kind>; 8]| -> Vec<usize> {
+            (0..8).filter(|x| row[*x].is_none()).collect()
+        };
+        let queen = empty(&row)[index / 16 % 6];
+        row[queen] = Some(Kind::Queen);
+        let (first, second) = KNIGHTS[index / 96];
+        let squares = empty(&row);
+        row[squares[first]] = Some(Kind::Knight);
+        row[squares[second]] = Some(Kind::Knight);
+        let squares = empty(&row);
+        for (x, kind) in squares.iter().zip([Kind::Rook, Kind::King, Kind::Rook]) {
+            row[*x] = Some(kind);
+        }
+
+        let mut chess = Chess::new();
+        for (x, kind) in row.into_iter().enumerate() {
+            for color in [Color::White, Color::Black] {
+                let pos = Pos::new(x as isize, color.back_rank());
+                chess.set(pos, kind.map(|kind| Piece::new(color, kind)));
+            }
+        }
+        chess.kings = [
+            Pos::new(squares[1] as isize, Color::White.back_rank()),
+            Pos::new(squares[1] as isize, Color::Black.back_rank()),
+        ];
+        chess.castling_columns = [[squares[2] as isize, squares[0] as isize]; 2];
+        chess.chess960 = true;
+        chess
+    }
+
+    /// Returns a board without any pieces or castling rights, with white to move. Pieces can be
+    /// placed with [`set`], after which `kings` has to be set to the positions of the kings.
+    ///
+    /// [`set`]: #method.set
+    pub fn empty() -> Self {
+        let mut chess = Chess::new();
+        for pos in Self::board_positions() {
+            chess.set(pos, None);
+        }
+        chess.castling_rights = [CastlingRights {
+            king_side: false,
+            queen_side: false,
+        }; 2];
+        chess
+    }
+
+    /// Returns the pieces on the board, stored by row and then by column.
+    pub fn board(&self) -> &[[Option<Piece>; 8]; 8] {
+        &self.board
+    }
+
+    /// Places a piece on a position on the board or in a graveyard, or clears the position when
+    /// the piece is `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the position is neither on the board nor in a graveyard.
+    pub fn set(&mut self, pos: Pos, piece: Option<Piece>) {
+        if Self::on_board(&pos) {
+            let bit = bitboard::bit(pos);
+            if let Some(old) = self[pos] {
+                self.colors[old.color.king_index()] &= !bit;
+                self.kinds[old.kind.index()] &= !bit;
+                self.pieces_key ^= zobrist::piece(old, pos);
+            }
+            if let Some(new) = piece {
+                self.colors[new.color.king_index()] |= bit;
+                self.kinds[new.kind.index()] |= bit;
+                self.pieces_key ^= zobrist::piece(new, pos);
+            }
+            self.board[pos.y() as usize][pos.x() as usize] = piece;
+        } else if let Some((graveyard, row, column)) = Self::graveyard_index(&pos) {
+            self.graveyards[graveyard].graveyard[row][column] = piece;
+        } else {
+            panic!("{pos:?} is neither on the board nor in a graveyard");
         }
     }
 
+    /// Removes the piece on a position and returns it.
+    pub fn take(&mut self, pos: Pos) -> Option<Piece> {
+        let piece = self[pos];
+        self.set(pos, None);
+        piece
+    }
+
+    /// Moves whatever stands on one position to another, without checking any rules.
+    pub fn move_piece(&mut self, from: Pos, to: Pos) {
+        let piece = self.take(from);
+        self.set(to, piece);
+    }
+
     /// Checks whether a given position is on the board.
-    fn on_board(pos: &Pos) -> bool {
+    pub(crate) fn on_board(pos: &Pos) -> bool {
         (0 <= pos.x() && pos.x() < 8) && (0 <= pos.y() && pos.y() < 8)
     }
 
@@ -111,13 +340,51 @@ impl Chess {
     }
     /// Returns an iterator over all positions of a graveyard of the current player.
     pub fn graveyard_positions(&self) -> impl Iterator<Item = Pos> {
-        let mut start = 9;
-        let mut end = 9;
-        if self.turn == Color::White {
-            start = -2;
-            end = -2;
+        Self::graveyard_of(!self.turn)
+    }
+
+    /// Returns an iterator over all positions of the graveyard the captured pieces of the given
+    /// color are put in.
+    pub fn graveyard_of(color: Color) -> impl Iterator<Item = Pos> {
+        color
+            .graveyard_columns()
+            .into_iter()
+            .flat_map(|x| (-1..=8).map(move |y| Pos::new(x, y)))
+    }
+
+    /// Returns the index of the graveyard, and the row and column within it for a position next
+    /// to the board, or `None` when the position is not part of a graveyard.
+    fn graveyard_index(pos: &Pos) -> Option<(usize, usize, usize)> {
+        if !(-1..=8).contains(&pos.y()) {
+            return None;
         }
-        (start..=end).flat_map(|x| (-1..=8).map(move |y| Pos::new(x, y)))
+        [Color::White, Color::Black].into_iter().find_map(|color| {
+            let column = color
+                .graveyard_columns()
+                .iter()
+                .position(|x| *x == pos.x())?;
+            Some((color.king_index(), (pos.y() + 1) as usize, column))
+        })
+    }
+
+    /// Finds the empty graveyard position closest to the given position for a captured piece.
+    /// The column next to the board is filled up first, so the outer column is only used when
+    /// the pieces have to pass the inner one anyway. Returns `None` when the graveyard is full.
+    pub fn graveyard_slot(&self, piece: Piece, near: Pos) -> Option<Pos> {
+        piece.color.graveyard_columns().into_iter().find_map(|x| {
+            (-1..=8)
+                .map(|y| Pos::new(x, y))
+                .filter(|pos| self[pos].is_none())
+                .min_by(|a, b| a.distance(near).partial_cmp(&b.distance(near)).unwrap())
+        })
+    }
+
+    /// Finds the graveyard position closest to the given position that holds the given piece.
+    /// Returns `None` when no such piece has been captured.
+    pub fn graveyard_find(&self, piece: Piece, near: Pos) -> Option<Pos> {
+        Self::graveyard_of(piece.color)
+            .filter(|pos| self[pos] == Some(piece))
+            .min_by(|a, b| a.distance(near).partial_cmp(&b.distance(near)).unwrap())
     }
     /// Returns an iterator over all positions around the board.
     pub fn border_positions() -> impl Iterator<Item = Pos> {
@@ -135,188 +402,574 @@ impl Chess {
 
     /// Returns an iterator over all pieces on the board.
     fn pieces(&self) -> impl Iterator<Item = (Pos, Piece)> + '_ {
-        Self::board_positions().filter_map(|pos| self[pos].map(|piece| (pos, piece)))
+        bitboard::positions(self.occupied()).map(|pos| (pos, self[pos].unwrap()))
+    }
+
+    /// Returns the squares of all pieces on the board.
+    fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
     }
 
-    /// Generates all legal moves for the current player.
+    /// Returns the squares of all pieces of the given color.
+    pub(crate) fn color_squares(&self, color: Color) -> Bitboard {
+        self.colors[color.king_index()]
+    }
+
+    /// Returns the squares of all pieces of the given color and kind.
+    pub(crate) fn piece_squares(&self, piece: Piece) -> Bitboard {
+        self.colors[piece.color.king_index()] & self.kinds[piece.kind.index()]
+    }
+
+    /// Sets the variant the game is played by. A variant without a royal king has no castling,
+    /// so the castling rights are cleared.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        if !rules.variant().royal_king() {
+            self.castling_rights = [CastlingRights {
+                king_side: false,
+                queen_side: false,
+            }; 2];
+        }
+    }
+
+    /// Generates all legal moves for the current player, by the rules of the variant in `rules`.
     pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
-        self.unsafe_moves(self.turn).filter(|m| self.is_safe(*m))
+        let variant = self.rules.variant();
+        let mut moves = self.unsafe_moves(self.turn);
+        if variant.royal_king() {
+            // Unless the king is checked, only a move of the king itself or of a piece on a line
+            // with it can expose the king. An en passant capture also removes a second piece from
+            // the board.
+            let king = self.kings[self.turn.king_index()];
+            let lines = if self.is_checked(self.turn) {
+                !0
+            } else {
+                bitboard::bit(king)
+                    | bitboard::rook_attacks(king, 0)
+                    | bitboard::bishop_attacks(king, 0)
+            };
+            moves.retain(|m| {
+                (lines & bitboard::bit(m.from) == 0 && Some(m.to) != self.en_passant)
+                    || self.is_safe(*m)
+            });
+        }
+        variant.filter_moves(self, &mut moves);
+        moves.into_iter()
     }
 
     /// Checks whether performing a move does not check the current player's own king.
+    ///
+    /// The move is not performed for this. Only the occupied squares and the attacking pieces are
+    /// updated, after which the square of the king is checked for attacks.
     fn is_safe(&self, m: Move) -> bool {
-        let mut copy = *self;
-        copy.perform(m);
-        !copy.is_checked(self.turn)
+        let piece = match self[m.from] {
+            Some(piece) => piece,
+            None => return false,
+        };
+        let player = piece.color;
+        let to = bitboard::bit(m.to);
+        let mut occupied = (self.occupied() & !bitboard::bit(m.from)) | to;
+        let mut attackers = self.colors[(!player).king_index()] & !to;
+        // A pawn captured en passant does not stand on the destination of the move.
+        if let Some(captured) = self.captured_pos(m).filter(|captured| *captured != m.to) {
+            occupied &= !bitboard::bit(captured);
+            attackers &= !bitboard::bit(captured);
+        }
+        let castling = self.castling(m);
+        if let Some(Castling { king, rook }) = castling {
+            occupied = (self.occupied() & !bitboard::bit(king.from) & !bitboard::bit(rook.from))
+                | bitboard::bit(king.to)
+                | bitboard::bit(rook.to);
+        }
+        let king = if let Some(castling) = castling {
+            castling.king.to
+        } else if piece.kind == Kind::King {
+            m.to
+        } else {
+            self.kings[player.king_index()]
+        };
+        !self.is_attacked_by(king, !player, attackers, occupied)
     }
 
     /// Generates all moves for the given player, even those that would check their own king.
-    /// Each kind of piece has their own set of moves, the function starts by filtering out
-    /// all pieces that are not of the current players color. After that all moves are  
-    fn unsafe_moves(&self, player: Color) -> impl Iterator<Item = Move> + '_ {
-        self.pieces()
-            .filter(move |(_, piece)| piece.color == player)
-            .flat_map(move |(from, piece)| match piece.kind {
-                Kind::Pawn => {
-                    // A pawn can move one step straight towards the other side of the board, or diagonal
-                    // when capturing an enemy piece
-                    let (step, captures, start_row) = match player {
-                        Color::Black => (Shift::UP, vec![Shift::UP_LEFT, Shift::UP_RIGHT], 6),
-                        Color::White => (Shift::DOWN, vec![Shift::DOWN_RIGHT, Shift::DOWN_LEFT], 1),
-                    };
-                    // A pawn can only capture  an enemy piece that is diagonally in front it.
-                    let captures = captures
-                        .into_iter()
-                        .map(move |dir| from + dir)
-                        .filter(move |to| {
-                            self[to]
-                                .as_ref()
-                                .map(|piece| piece.color != player)
-                                .unwrap_or_default()
-                        })
-                        .map(move |to| Move::new(from, to));
-
-                    let to = from + step;
-                    let too = from + step * 2;
-                    // a pawn can move one step towards the other side of the board
-                    let step =
-                        (self[to].is_none() && Self::on_board(&to)).then(|| Move::new(from, to));
-                    // Or two steps when it is on its starting position
-                    let leap = (from.y() == start_row && self[to].is_none() && self[too].is_none())
-                        .then(|| Move::new(from, too));
-
-                    Box::new(captures.chain(leap).chain(step)) as Box<dyn Iterator<Item = Move>>
+    /// Each kind of piece has their own set of moves, which are looked up in the attack tables of
+    /// [`bitboard`] for the squares of all pieces of that kind. A piece may move to any of those
+    /// squares, except the ones occupied by its own pieces.
+    fn unsafe_moves(&self, player: Color) -> Vec<Move> {
+        let own = self.colors[player.king_index()];
+        let enemies = self.colors[(!player).king_index()];
+        let occupied = own | enemies;
+        let mut moves = Vec::with_capacity(64);
+
+        // A pawn can move one step straight towards the other side of the board, or two from its
+        // starting row. It can only capture an enemy piece that is diagonally in front it, or a
+        // pawn that just passed that square with a double step (en passant).
+        let last_row = (!player).back_rank();
+        let (step, start_row) = match player {
+            Color::Black => (Shift::UP, 6),
+            Color::White => (Shift::DOWN, 1),
+        };
+        let en_passant = match self.en_passant {
+            Some(pos) if player == self.turn => bitboard::bit(pos),
+            _ => 0,
+        };
+        for from in bitboard::positions(own & self.kinds[Kind::Pawn.index()]) {
+            let mut targets = bitboard::pawn_attacks(player, from) & (enemies | en_passant);
+            let to = from + step;
+            if Self::on_board(&to) && occupied & bitboard::bit(to) == 0 {
+                targets |= bitboard::bit(to);
+                let too = to + step;
+                if from.y() == start_row && occupied & bitboard::bit(too) == 0 {
+                    targets |= bitboard::bit(too);
+                }
+            }
+            // A pawn that reaches the last row is promoted, which can be done to any of the
+            // promotion kinds.
+            for to in bitboard::positions(targets) {
+                let promotions: &[Option<Kind>] = if to.y() == last_row {
+                    &Move::PROMOTIONS
+                } else {
+                    &[None]
+                };
+                moves.extend(promotions.iter().map(|promotion| Move {
+                    promotion: *promotion,
+                    ..Move::new(from, to)
+                }));
+            }
+        }
+
+        for from in bitboard::positions(own & !self.kinds[Kind::Pawn.index()]) {
+            let square = bitboard::square(from);
+            let targets = match self[from].unwrap().kind {
+                // A knight moves in L shaped jumps in all directions, over any other pieces.
+                Kind::Knight => KNIGHT_ATTACKS[square],
+                // The rook, bishop and queen move over empty squares in a straight line, and can
+                // capture the first piece in their way.
+                Kind::Rook => bitboard::rook_attacks(from, occupied),
+                Kind::Bishop => bitboard::bishop_attacks(from, occupied),
+                Kind::Queen => {
+                    bitboard::rook_attacks(from, occupied)
+                        | bitboard::bishop_attacks(from, occupied)
+                }
+                // The king can move one square in any direction.
+                Kind::King => KING_ATTACKS[square],
+                Kind::Pawn => unreachable!(),
+            };
+            moves.extend(bitboard::positions(targets & !own).map(|to| Move::new(from, to)));
+        }
+
+        // Castling protects the king, so a variant without a royal king has no castling.
+        if self.rules.variant().royal_king() {
+            moves.extend(self.castling_moves(player));
+        }
+        moves
+    }
+
+    /// Generates the castling moves for the given player. A castling move is stored as the king
+    /// moving two squares towards the rook, or in Chess960 as the king moving onto the rook. The
+    /// moves of both pieces are derived from that with [`castling`].
+    ///
+    /// The player may castle to a side when it still has the right to do so, all squares the king
+    /// and the rook pass or land on are empty except for the two of them, and the king is not
+    /// checked, nor passes or lands on a square that is attacked by the opponent.
+    ///
+    /// [`castling`]: #method.castling
+    fn castling_moves(&self, player: Color) -> impl Iterator<Item = Move> + '_ {
+        let rights = self.castling_rights[player.king_index()];
+        let king = self.kings[player.king_index()];
+        let row = player.back_rank();
+        let rook = Some(Piece::new(player, Kind::Rook));
+        let [king_side, queen_side] = self.castling_columns[player.king_index()];
+        // (right, rook column, king destination, rook destination)
+        let sides = [
+            (rights.king_side, king_side, 6, 5),
+            (rights.queen_side, queen_side, 2, 3),
+        ];
+        sides
+            .into_iter()
+            .filter(move |&(right, rook_x, king_to, rook_to)| {
+                let columns = [king.x(), rook_x, king_to, rook_to];
+                let passed = *columns.iter().min().unwrap()..=*columns.iter().max().unwrap();
+                let mut walked = king.x().min(king_to)..=king.x().max(king_to);
+                right
+                    && king.y() == row
+                    && (self.chess960 || king.x() == 4)
+                    && self[Pos::new(rook_x, row)] == rook
+                    && passed
+                        .filter(|x| *x != king.x() && *x != rook_x)
+                        .all(|x| self[Pos::new(x, row)].is_none())
+                    && walked.all(|x| !self.is_attacked(Pos::new(x, row), !player))
+            })
+            .map(move |(_, rook_x, king_to, _)| {
+                if self.chess960 {
+                    Move::new(king, Pos::new(rook_x, row))
+                } else {
+                    Move::new(king, Pos::new(king_to, row))
                 }
-                Kind::Rook => Box::new(Shift::CARDINAL_DIRS.iter().flat_map(move |dir| {
-                    //A rook can move straight over all traversable squares in a straight line across the board.
-                    //It can capture an enemy piece when a straight path is clear between the rook and the piece.
-                    let mut capture = false;
-                    (1..)
-                        .map(move |distance| from + *dir * distance)
-                        .take_while(Self::on_board)
-                        .take_while(move |to| self.is_traversable(player, to, &mut capture))
-                        .map(move |to| Move::new(from, to))
-                })),
-                Kind::Knight => Box::new(
-                    // A knight moves in L shaped jumps in all directions. It can jump over other friendly or enemy pieces
-                    // It can capture an enemy piece when it jumps on it
-                    Shift::JUMPS
-                        .iter()
-                        .map(move |jump| from + *jump)
-                        .filter(Self::on_board)
-                        .filter(move |to| {
-                            self[to]
-                                .as_ref()
-                                .map(|piece| piece.color != player)
-                                .unwrap_or(true)
-                        })
-                        .map(move |to| Move::new(from, to)),
-                ),
-                Kind::Bishop => Box::new(Shift::DIAGONAL_DIRS.iter().flat_map(move |dir| {
-                    //A bishop can move diagonally over all traversable squares in a straight line across the board.
-                    //It can capture an enemy piece when a straight path is clear between the rook and the piece.
-                    let mut capture = false;
-                    (1..)
-                        .map(move |distance| from + *dir * distance)
-                        .take_while(Self::on_board)
-                        .take_while(move |to| self.is_traversable(player, to, &mut capture))
-                        .map(move |to| Move::new(from, to))
-                })),
-                Kind::Queen => Box::new(Shift::DIRS.iter().flat_map(move |dir| {
-                    //The queen can move over all traversable squares in all directions.
-                    let mut capture = false;
-                    (1..)
-                        .map(move |distance| from + *dir * distance)
-                        .take_while(Self::on_board)
-                        .take_while(move |to| self.is_traversable(player, to, &mut capture))
-                        .map(move |to| Move::new(from, to))
-                })),
-                Kind::King => Box::new(
-                    //The king can move one square in any direction.
-                    Shift::DIRS
-                        .iter()
-                        .map(move |dir| from + *dir)
-                        .filter(Self::on_board)
-                        .filter(move |to| {
-                            self[to]
-                                .as_ref()
-                                .map(|piece| piece.color != player)
-                                .unwrap_or(true)
-                        })
-                        .map(move |to| Move::new(from, to)),
-                ),
             })
     }
 
-    /// Performs a move, changing the board state.
-    pub fn perform(&mut self, m: Move) {
-        if self[m.from].unwrap().kind == Kind::King {
-            self.kings[self.turn.king_index()] = m.to;
+    /// Returns the moves of the king and the rook when the given move is a castling move, and
+    /// `None` otherwise.
+    pub fn castling(&self, m: Move) -> Option<Castling> {
+        let piece = self[m.from]?;
+        if piece.kind != Kind::King || m.from.y() != m.to.y() {
+            return None;
         }
-        self[m.to] = self[m.from].take();
-        self.turn = !self.turn;
+        let row = m.from.y();
+        let rook_x = if self.chess960 {
+            if self[m.to] != Some(Piece::new(piece.color, Kind::Rook)) {
+                return None;
+            }
+            m.to.x()
+        } else if (m.to - m.from).dx().abs() == 2 {
+            let [king_side, queen_side] = self.castling_columns[piece.color.king_index()];
+            if m.to.x() > m.from.x() {
+                king_side
+            } else {
+                queen_side
+            }
+        } else {
+            return None;
+        };
+        let (king_to, rook_to) = if rook_x > m.from.x() { (6, 5) } else { (2, 3) };
+        Some(Castling {
+            king: Move::new(m.from, Pos::new(king_to, row)),
+            rook: Move::new(Pos::new(rook_x, row), Pos::new(rook_to, row)),
+        })
+    }
+
+    /// Returns the piece moves that physically perform a castling, in the order they have to be
+    /// made. Usually the king moves first and the rook follows, or the other way around when the
+    /// king has to move onto the square of the rook. When the king and the rook swap squares, the
+    /// rook waits on the nearest free graveyard position while the king passes. Pieces that
+    /// already stand on their destination do not move.
+    pub fn castling_steps(&self, castling: Castling) -> Vec<Move> {
+        let Castling { king, rook } = castling;
+        let steps = if king.to != rook.from {
+            vec![king, rook]
+        } else if rook.to != king.from {
+            vec![rook, king]
+        } else {
+            let slot = Self::graveyard_of(Color::White)
+                .chain(Self::graveyard_of(Color::Black))
+                .filter(|pos| self[pos].is_none())
+                .min_by(|a, b| {
+                    a.distance(rook.from)
+                        .partial_cmp(&b.distance(rook.from))
+                        .unwrap()
+                });
+            match slot {
+                Some(slot) => vec![Move::new(rook.from, slot), king, Move::new(slot, rook.to)],
+                None => vec![king, rook],
+            }
+        };
+        steps
+            .into_iter()
+            .filter(|step| step.from != step.to)
+            .collect()
     }
 
-    /// Evaluates how many "points" a board state is worth. A positive score indicates that white is
-    /// in a favorable position, and a negative score indicates that black is currently better off.
-    pub fn evaluate(&self) -> i16 {
-        match self.outcome() {
-            None => self.pieces().map(|(_, piece)| piece.base_value()).sum(),
-            Some(outcome) => outcome.value(),
+    /// Returns the position of the piece that is captured by the given move, if any. This is the
+    /// destination of the move, except for en passant where the captured pawn stands next to it.
+    pub fn captured_pos(&self, m: Move) -> Option<Pos> {
+        let piece = self[m.from]?;
+        if matches!(self[m.to], Some(target) if target.color != piece.color) {
+            Some(m.to)
+        } else if piece.kind == Kind::Pawn && self.en_passant == Some(m.to) {
+            Some(Pos::new(m.to.x(), m.from.y()))
+        } else {
+            None
         }
     }
 
-    /// For pieces that can move entire rows, lanes, or diagonals, this function checks whether they
-    /// can continue moving in a straight line.
-    ///
-    /// Pieces can move as long a tiles are empty, but must stop immediately upon encountering a
-    /// piece of the same color. They can continue if they encounter a piece of the opposite color
-    /// to capture it, but cannot continue after that. This is what the `capture` variable is used
-    /// for; it should be set to false at the start of the row, lane, or diagonal, and this function
-    /// will set it to true as soon as a piece of the opposite color is detected, stopping on the
-    /// next tile.
-    ///
-    /// Note that this function does not check whether the destination is on the board or not.
-    /// [`on_board`] can be used first for that purpose.
+    /// Performs a move, changing the board state. The move is added to the history, so it can be
+    /// taken back with [`undo`].
     ///
-    /// [`on_board`]: #method.on_board
-    fn is_traversable(&self, player: Color, to: &Pos, capture: &mut bool) -> bool {
-        if *capture {
-            false
+    /// [`undo`]: #method.undo
+    pub fn perform(&mut self, m: Move) {
+        let piece = self[m.from].unwrap();
+        let mut undo = Undo {
+            m,
+            piece,
+            capture: None,
+            castling: self.castling(m),
+            promotion_swap: None,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            checks: self.checks,
+            key: self.position_key(),
+        };
+        if let Some(pos) = self.captured_pos(m) {
+            let captured = self.take(pos).unwrap();
+            let slot = self.graveyard_slot(captured, pos);
+            if let Some(slot) = slot {
+                self.set(slot, Some(captured));
+            }
+            undo.capture = Some(Capture {
+                piece: captured,
+                pos,
+                slot,
+            });
+        }
+        if piece.kind == Kind::Pawn || undo.capture.is_some() {
+            self.halfmove_clock = 0;
         } else {
-            match &self[to] {
-                None => true,
-                Some(piece) => {
-                    if piece.color == player {
-                        false
-                    } else {
-                        *capture = true;
-                        true
-                    }
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.en_passant = None;
+        if piece.kind == Kind::Pawn && (m.to - m.from).dy().abs() == 2 {
+            self.en_passant = Some(Pos::new(m.from.x(), (m.from.y() + m.to.y()) / 2));
+        }
+        if piece.kind == Kind::King {
+            self.kings[piece.color.king_index()] = match undo.castling {
+                Some(castling) => castling.king.to,
+                None => m.to,
+            };
+            self.castling_rights[piece.color.king_index()] = CastlingRights {
+                king_side: false,
+                queen_side: false,
+            };
+        }
+        // Moving a rook away from its starting square, or capturing the rook on it, loses that
+        // right.
+        for pos in [m.from, m.to] {
+            for color in [Color::White, Color::Black] {
+                let rights = &mut self.castling_rights[color.king_index()];
+                let [king_side, queen_side] = self.castling_columns[color.king_index()];
+                if pos == Pos::new(king_side, color.back_rank()) {
+                    rights.king_side = false;
+                } else if pos == Pos::new(queen_side, color.back_rank()) {
+                    rights.queen_side = false;
                 }
             }
         }
+        match undo.castling {
+            // Both pieces are taken off first, as they may land on each other's square.
+            Some(Castling { king, rook }) => {
+                let (king_piece, rook_piece) = (self.take(king.from), self.take(rook.from));
+                self.set(king.to, king_piece);
+                self.set(rook.to, rook_piece);
+            }
+            None => self.move_piece(m.from, m.to),
+        }
+        if let Some(kind) = m.promotion {
+            let promoted = Piece::new(piece.color, kind);
+            undo.promotion_swap = self.promotion_swap(m.to, promoted);
+            match undo.promotion_swap {
+                Some((pawn_move, spare_move)) => {
+                    self.move_piece(pawn_move.from, pawn_move.to);
+                    self.move_piece(spare_move.from, spare_move.to);
+                }
+                // Without a spare piece the pawn stays on the board in place of the promoted piece.
+                None => self.set(m.to, Some(promoted)),
+            }
+        }
+        self.turn = !self.turn;
+        if self.rules.variant().counts_checks() && self.is_checked(self.turn) {
+            self.checks[(!self.turn).king_index()] += 1;
+        }
+        self.history.push(undo);
+    }
+
+    /// Takes back the last performed move. Captured pieces return from the graveyard, a promoted
+    /// piece is swapped back for the pawn, and the positions of the kings, the castling and en
+    /// passant rights and the move clocks are restored. Returns the move that was taken back, or
+    /// `None` when no move has been performed yet.
+    pub fn undo(&mut self) -> Option<Move> {
+        let undo = self.history.pop()?;
+        let m = undo.m;
+        self.turn = !self.turn;
+        if self.turn == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        if m.promotion.is_some() {
+            match undo.promotion_swap {
+                Some((pawn_move, spare_move)) => {
+                    self.move_piece(spare_move.to, spare_move.from);
+                    self.move_piece(pawn_move.to, pawn_move.from);
+                }
+                None => self.set(m.to, Some(undo.piece)),
+            }
+        }
+        match undo.castling {
+            Some(Castling { king, rook }) => {
+                let (king_piece, rook_piece) = (self.take(king.to), self.take(rook.to));
+                self.set(king.from, king_piece);
+                self.set(rook.from, rook_piece);
+            }
+            None => self.move_piece(m.to, m.from),
+        }
+        if let Some(capture) = undo.capture {
+            if let Some(slot) = capture.slot {
+                self.set(slot, None);
+            }
+            self.set(capture.pos, Some(capture.piece));
+        }
+        if undo.piece.kind == Kind::King {
+            self.kings[undo.piece.color.king_index()] = m.from;
+        }
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.checks = undo.checks;
+        Some(m)
+    }
+
+    /// Returns the piece moves that physically take back the last performed move, in the order
+    /// they have to be made. A promoted piece goes back to the graveyard and the pawn returns
+    /// first, then the moved piece returns, or the steps of a castling are made backwards, and
+    /// finally a captured piece is pulled back out of the graveyard.
+    pub fn undo_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        if let Some(undo) = self.history.last() {
+            if let Some((pawn_move, spare_move)) = undo.promotion_swap {
+                moves.push(Move::new(spare_move.to, spare_move.from));
+                moves.push(Move::new(pawn_move.to, pawn_move.from));
+            }
+            match undo.castling {
+                Some(castling) => moves.extend(
+                    self.castling_steps(castling)
+                        .into_iter()
+                        .rev()
+                        .map(|step| Move::new(step.to, step.from)),
+                ),
+                None => moves.push(Move::new(undo.m.to, undo.m.from)),
+            }
+            if let Some(Capture {
+                pos,
+                slot: Some(slot),
+                ..
+            }) = undo.capture
+            {
+                moves.push(Move::new(slot, pos));
+            }
+        }
+        moves
+    }
+
+    /// Returns the moves that physically promote the pawn on the given square: the pawn moves to
+    /// the nearest free position in its graveyard, and the nearest captured piece of the promoted
+    /// kind takes its place. Returns `None` when no such piece has been captured yet, or when the
+    /// graveyard is full.
+    pub fn promotion_swap(&self, square: Pos, promoted: Piece) -> Option<(Move, Move)> {
+        let pawn = self[square]?;
+        let spare = self.graveyard_find(promoted, square)?;
+        let slot = self.graveyard_slot(pawn, square)?;
+        Some((Move::new(square, slot), Move::new(spare, square)))
+    }
+
+    /// Adds up the base values of the pieces on the board, the pieces of black counting negative.
+    /// A positive score indicates that white has more material, and a negative score that black
+    /// has.
+    pub fn material(&self) -> i16 {
+        self.pieces().map(|(_, piece)| piece.base_value()).sum()
     }
 
     /// Checks whether the given player is currently checked.
-    fn is_checked(&self, player: Color) -> bool {
+    pub(crate) fn is_checked(&self, player: Color) -> bool {
         let king = self.kings[player.king_index()];
-        self.unsafe_moves(!player).any(|m| m.to == king)
+        self.is_attacked(king, !player)
+    }
+
+    /// Checks whether a piece of the given player could capture on the given position.
+    ///
+    /// Instead of generating all moves of the attacker, this function looks outwards from the
+    /// position: for a knight jump away, a pawn or king next to it, or the first piece in a
+    /// straight line. Because it does not use [`unsafe_moves`], it can also be used to generate
+    /// castling moves.
+    ///
+    /// [`unsafe_moves`]: #method.unsafe_moves
+    fn is_attacked(&self, pos: Pos, by: Color) -> bool {
+        self.is_attacked_by(pos, by, self.colors[by.king_index()], self.occupied())
+    }
+
+    /// Checks whether one of the given attacking pieces of a player could capture on the given
+    /// position, when the given squares are occupied. This makes it possible to look for attacks
+    /// after a move without performing it.
+    fn is_attacked_by(&self, pos: Pos, by: Color, attackers: Bitboard, occupied: Bitboard) -> bool {
+        let of_kind = |kind: Kind| self.kinds[kind.index()] & attackers;
+        let square = bitboard::square(pos);
+        // The pawns that attack this position stand where a pawn of the other color on this
+        // position would attack.
+        bitboard::pawn_attacks(!by, pos) & of_kind(Kind::Pawn) != 0
+            || KNIGHT_ATTACKS[square] & of_kind(Kind::Knight) != 0
+            || KING_ATTACKS[square] & of_kind(Kind::King) != 0
+            || bitboard::rook_attacks(pos, occupied) & (of_kind(Kind::Rook) | of_kind(Kind::Queen))
+                != 0
+            || bitboard::bishop_attacks(pos, occupied)
+                & (of_kind(Kind::Bishop) | of_kind(Kind::Queen))
+                != 0
+    }
+
+    /// Returns a key that is the same for two positions with the same pieces on the board, the
+    /// same player to move, the same castling and en passant rights and the same number of
+    /// checks given. Positions are repeated when their keys are equal.
+    ///
+    /// This is a Zobrist key: the key of the pieces is updated with every piece that is placed or
+    /// removed, so only the few other features have to be added to it.
+    pub fn position_key(&self) -> u64 {
+        self.pieces_key
+            ^ zobrist::state(self.turn, &self.castling_rights, self.en_passant)
+            ^ zobrist::checks(&self.checks)
+    }
+
+    /// Counts how often the current position has occurred in this game, including this time.
+    /// A capture or pawn move can never be undone, so only the positions since the last one of
+    /// those are compared.
+    pub fn repetitions(&self) -> usize {
+        // Each player has to move away and back again before a position can repeat.
+        if self.halfmove_clock < 4 {
+            return 1;
+        }
+        let key = self.position_key();
+        let earlier = self
+            .history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .filter(|undo| undo.key == key)
+            .count();
+        earlier + 1
+    }
+
+    /// Checks whether neither player has enough pieces left to ever checkmate. That is the case
+    /// when only the kings are left, when one side has a single bishop or knight besides its king,
+    /// or when all remaining pieces besides the kings are bishops on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let of_kind = |kind: Kind| self.kinds[kind.index()];
+        if of_kind(Kind::Pawn) | of_kind(Kind::Rook) | of_kind(Kind::Queen) != 0 {
+            return false;
+        }
+        let minor = of_kind(Kind::Knight) | of_kind(Kind::Bishop);
+        // The squares on which the sum of the row and column is even, like a1.
+        let dark_squares: Bitboard = 0xAA55_AA55_AA55_AA55;
+        minor.count_ones() <= 1
+            || of_kind(Kind::Knight) == 0
+                && (minor & dark_squares == 0 || minor & !dark_squares == 0)
     }
 
     /// Returns the outcome of the game state. A `None` output indicates that the game is not over,
-    /// whereas `Some(Outcome)` indicates which player has won the game, or if there was a
-    /// stalemate.
+    /// whereas `Some(Outcome)` indicates which player has won the game, or why it is a draw.
+    /// The variant in `rules` may end the game first, and decides who wins when the current
+    /// player has no legal moves.
     pub fn outcome(&self) -> Option<Outcome> {
-        if self.moves().next().is_none() {
+        let variant = self.rules.variant();
+        if let Some(outcome) = variant.outcome(self) {
+            Some(outcome)
+        } else if self.moves().next().is_none() {
             // No legal moves for the current player, the game is over
-            if self.is_checked(self.turn) {
-                // The current player is checked, so the other player wins
-                Some(Outcome::Winner(!self.turn))
-            } else {
-                // The current player is not checked, so it's a stalemate
-                Some(Outcome::Stalemate)
-            }
+            Some(variant.no_moves(self))
+        } else if variant.is_insufficient_material(self) {
+            Some(Outcome::Draw(Draw::InsufficientMaterial))
+        } else if self.halfmove_clock >= 100 {
+            Some(Outcome::Draw(Draw::FiftyMoves))
+        } else if self.repetitions() >= 3 {
+            Some(Outcome::Draw(Draw::Repetition))
         } else {
             // There are moves left for the current player, so the game is not over yet
             None
@@ -340,36 +993,78 @@ where
         let pos = index.borrow();
         if Self::on_board(pos) {
             &self.board[pos.y() as usize][pos.x() as usize]
+        } else if let Some((graveyard, row, column)) = Self::graveyard_index(pos) {
+            &self.graveyards[graveyard].graveyard[row][column]
         } else {
             &None
         }
     }
 }
 
-impl<P> IndexMut<P> for Chess
-where
-    P: Borrow<Pos>,
-{
-    fn index_mut(&mut self, index: P) -> &mut Self::Output {
-        &mut self.board[index.borrow().y() as usize][index.borrow().x() as usize]
-    }
-}
-
-///Winner or Stalemate
+///Winner, Stalemate or another kind of draw
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Outcome {
     Winner(Color),
     Stalemate,
+    Draw(Draw),
+    /// The time of the player of this color ran out, so they lost the game.
+    OutOfTime(Color),
+    /// The player of this color gave up the game.
+    Resigned(Color),
 }
 
 impl Outcome {
-    pub fn value(&self) -> i16 {
+    /// The player who won the game, or `None` when it is a draw.
+    pub fn winner(&self) -> Option<Color> {
         match self {
-            Outcome::Winner(color) => match color {
-                Color::Black => i16::MIN,
-                Color::White => i16::MAX,
-            },
-            Outcome::Stalemate => 0,
+            Outcome::Winner(color) => Some(*color),
+            Outcome::OutOfTime(color) | Outcome::Resigned(color) => Some(!*color),
+            Outcome::Stalemate | Outcome::Draw(_) => None,
+        }
+    }
+
+    /// The score of a position in which the game ended with this outcome.
+    pub fn value(&self) -> Score {
+        match self {
+            Outcome::Winner(color) => Score::checkmated(!*color),
+            Outcome::OutOfTime(color) | Outcome::Resigned(color) => Score::checkmated(*color),
+            Outcome::Stalemate | Outcome::Draw(_) => Score::DRAW,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Winner(color) => write!(f, "{color} wins"),
+            Outcome::Stalemate => write!(f, "Draw by stalemate"),
+            Outcome::Draw(draw) => write!(f, "Draw by {draw}"),
+            Outcome::OutOfTime(color) => write!(f, "{color} lost on time"),
+            Outcome::Resigned(color) => write!(f, "{color} resigned"),
+        }
+    }
+}
+
+/// The reasons a game can be drawn while the player to move still has legal moves.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Draw {
+    /// Neither player has enough pieces left to checkmate.
+    InsufficientMaterial,
+    /// Fifty moves of each player went by without a capture or a pawn move.
+    FiftyMoves,
+    /// The same position occurred for the third time.
+    Repetition,
+    /// The players agreed to a draw.
+    Agreement,
+}
+
+impl Display for Draw {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Draw::InsufficientMaterial => write!(f, "insufficient material"),
+            Draw::FiftyMoves => write!(f, "the fifty-move rule"),
+            Draw::Repetition => write!(f, "threefold repetition"),
+            Draw::Agreement => write!(f, "agreement"),
         }
     }
 }
@@ -416,12 +1111,29 @@ pub enum Color {
 }
 
 impl Color {
-    fn king_index(&self) -> usize {
+    pub(crate) fn king_index(&self) -> usize {
         match self {
             Color::Black => 1,
             Color::White => 0,
         }
     }
+
+    /// The row on which the pieces of this color start.
+    pub fn back_rank(&self) -> isize {
+        match self {
+            Color::Black => 7,
+            Color::White => 0,
+        }
+    }
+
+    /// The columns next to the board in which the captured pieces of this color are put, the
+    /// column closest to the board first.
+    pub fn graveyard_columns(&self) -> [isize; 2] {
+        match self {
+            Color::Black => [-2, -3],
+            Color::White => [9, 10],
+        }
+    }
 }
 
 impl Not for Color {
@@ -455,6 +1167,11 @@ pub enum Kind {
 }
 
 impl Kind {
+    /// The index of the kind in the bitboards of [`Chess`].
+    pub(crate) fn index(&self) -> usize {
+        *self as usize
+    }
+
     pub fn base_value(&self) -> i16 {
         match self {
             Kind::Pawn => 1,
@@ -465,6 +1182,31 @@ impl Kind {
             Kind::King => 0,
         }
     }
+
+    /// The letter used for this kind in FEN and in algebraic notation.
+    pub fn letter(&self) -> char {
+        match self {
+            Kind::Pawn => 'P',
+            Kind::Rook => 'R',
+            Kind::Knight => 'N',
+            Kind::Bishop => 'B',
+            Kind::Queen => 'Q',
+            Kind::King => 'K',
+        }
+    }
+
+    /// Returns the kind belonging to an uppercase letter, as used in FEN and algebraic notation.
+    pub fn from_letter(letter: char) -> Option<Kind> {
+        match letter {
+            'P' => Some(Kind::Pawn),
+            'R' => Some(Kind::Rook),
+            'N' => Some(Kind::Knight),
+            'B' => Some(Kind::Bishop),
+            'Q' => Some(Kind::Queen),
+            'K' => Some(Kind::King),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Kind {
@@ -481,15 +1223,29 @@ impl Display for Kind {
 }
 
 /// Move from a position to a position.
+/// A pawn that reaches the last row is promoted to the kind in `promotion`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Move {
     pub from: Pos,
     pub to: Pos,
+    pub promotion: Option<Kind>,
 }
 
 impl Move {
-    fn new(from: Pos, to: Pos) -> Self {
-        Move { from, to }
+    /// The kinds a pawn can be promoted to.
+    pub const PROMOTIONS: [Option<Kind>; 4] = [
+        Some(Kind::Queen),
+        Some(Kind::Rook),
+        Some(Kind::Bishop),
+        Some(Kind::Knight),
+    ];
+
+    pub fn new(from: Pos, to: Pos) -> Self {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
     }
 }
 
@@ -559,14 +1315,14 @@ mod tests {
 
         // Check that positions are within the expected range for the current player
         if chess.turn == Color::White {
-            assert_eq!(positions.len(), 10);
+            assert_eq!(positions.len(), 20);
             for pos in &positions {
-                assert!(pos.x() >= -2 && pos.x() <= -2 && pos.y() >= -1 && pos.y() <= 8);
+                assert!(pos.x() >= -3 && pos.x() <= -2 && pos.y() >= -1 && pos.y() <= 8);
             }
         } else {
-            assert_eq!(positions.len(), 10);
+            assert_eq!(positions.len(), 20);
             for pos in &positions {
-                assert!(pos.x() >= 9 && pos.x() <= 9 && pos.y() >= -1 && pos.y() <= 8);
+                assert!(pos.x() >= 9 && pos.x() <= 10 && pos.y() >= -1 && pos.y() <= 8);
             }
         }
     }
@@ -638,7 +1394,7 @@ mod tests {
         let chess = Chess::default();
 
         // Test that there are unsafe moves available
-        let unsafe_moves: Vec<Move> = chess.unsafe_moves(Color::White).collect();
+        let unsafe_moves = chess.unsafe_moves(Color::White);
         assert!(!unsafe_moves.is_empty());
     }
 
@@ -660,11 +1416,10 @@ mod tests {
     }
 
     #[test]
-    fn test_evaluate() {
+    fn test_material() {
         let chess = Chess::default();
-        let evaluation = chess.evaluate();
-        // In the initial state, it's common to have a positive evaluation for White.
-        assert!(evaluation >= 0);
+        // In the initial state, both players have the same pieces.
+        assert_eq!(chess.material(), 0);
     }
 
     #[test]
@@ -674,14 +1429,17 @@ mod tests {
         chess.perform(Move {
             from: Pos::new(5, 1),
             to: Pos::new(5, 2),
+            promotion: None,
         });
         chess.perform(Move {
             from: Pos::new(4, 6),
             to: Pos::new(4, 5),
+            promotion: None,
         });
         chess.perform(Move {
             from: Pos::new(6, 1),
             to: Pos::new(6, 3),
+            promotion: None,
         });
         chess.perform(Move::new(Pos::new(3, 7), Pos::new(7, 3)));
 
@@ -689,4 +1447,344 @@ mod tests {
         // Black won by checkmating white
         assert_eq!(chess.outcome(), Some(Outcome::Winner(Color::Black)));
     }
+
+    #[test]
+    fn test_castling() {
+        let mut chess = Chess::default();
+        // Clear the squares between the white king and both rooks.
+        for x in [1, 2, 3, 5, 6] {
+            chess.set(Pos::new(x, 0), None);
+        }
+        let moves: Vec<Move> = chess.moves().collect();
+        assert!(moves.contains(&Move::new(Pos::new(4, 0), Pos::new(6, 0))));
+        assert!(moves.contains(&Move::new(Pos::new(4, 0), Pos::new(2, 0))));
+
+        // Castling king side moves the rook over the king and removes both rights.
+        chess.perform(Move::new(Pos::new(4, 0), Pos::new(6, 0)));
+        assert_eq!(chess[Pos::new(6, 0)], Some(Piece::WHITE_KING));
+        assert_eq!(chess[Pos::new(5, 0)], Some(Piece::WHITE_ROOK));
+        assert_eq!(chess[Pos::new(7, 0)], None);
+        assert_eq!(chess.kings[0], Pos::new(6, 0));
+        assert!(!chess.castling_rights[0].king_side);
+        assert!(!chess.castling_rights[0].queen_side);
+        assert!(chess.castling_rights[1].king_side);
+    }
+
+    #[test]
+    fn test_chess960() {
+        let classical = Chess::chess960(518);
+        assert_eq!(classical.board(), Chess::default().board());
+        assert_eq!(classical.castling_columns, [[7, 0]; 2]);
+
+        let chess = Chess::chess960(0);
+        let row: String = (0..8)
+            .map(|x| chess[Pos::new(x, 7)].unwrap().kind.letter())
+            .collect();
+        assert_eq!(row, "BBQNNRKR");
+        assert_eq!(chess[Pos::new(6, 0)], Some(Piece::WHITE_KING));
+        assert_eq!(chess.kings, [Pos::new(6, 0), Pos::new(6, 7)]);
+        assert_eq!(chess.castling_columns, [[7, 5]; 2]);
+        assert_eq!(chess.moves().count(), 20);
+
+        // Every start position is different.
+        let mut rows: Vec<_> = (0..960)
+            .map(|index| Chess::chess960(index).board()[0])
+            .collect();
+        rows.sort();
+        rows.dedup();
+        assert_eq!(rows.len(), 960);
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        // The king on f1 and the rook on g1 swap squares when castling king side.
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R4KR1 w GA - 0 1").unwrap();
+        let king_side = Move::new(Pos::new(5, 0), Pos::new(6, 0));
+        let queen_side = Move::new(Pos::new(5, 0), Pos::new(0, 0));
+        let moves: Vec<Move> = chess.moves().collect();
+        assert!(moves.contains(&king_side));
+        assert!(moves.contains(&queen_side));
+        assert_eq!(chess.san(king_side), "O-O");
+
+        let castling = chess.castling(king_side).unwrap();
+        assert_eq!(castling.king, Move::new(Pos::new(5, 0), Pos::new(6, 0)));
+        assert_eq!(castling.rook, Move::new(Pos::new(6, 0), Pos::new(5, 0)));
+        // The rook waits next to the board while the king passes.
+        assert_eq!(
+            chess.castling_steps(castling),
+            vec![
+                Move::new(Pos::new(6, 0), Pos::new(9, 0)),
+                Move::new(Pos::new(5, 0), Pos::new(6, 0)),
+                Move::new(Pos::new(9, 0), Pos::new(5, 0)),
+            ]
+        );
+
+        let before = chess.clone();
+        chess.perform(king_side);
+        assert_eq!(chess[Pos::new(6, 0)], Some(Piece::WHITE_KING));
+        assert_eq!(chess[Pos::new(5, 0)], Some(Piece::WHITE_ROOK));
+        assert_eq!(chess.kings[0], Pos::new(6, 0));
+        assert_eq!(chess.history[0].capture, None);
+        assert_eq!(
+            chess.undo_moves(),
+            vec![
+                Move::new(Pos::new(5, 0), Pos::new(9, 0)),
+                Move::new(Pos::new(6, 0), Pos::new(5, 0)),
+                Move::new(Pos::new(9, 0), Pos::new(6, 0)),
+            ]
+        );
+        chess.undo();
+        assert_eq!(chess, before);
+
+        // Castling queen side moves the king over the square of the rook, which goes to d1.
+        chess.perform(queen_side);
+        assert_eq!(chess[Pos::new(2, 0)], Some(Piece::WHITE_KING));
+        assert_eq!(chess[Pos::new(3, 0)], Some(Piece::WHITE_ROOK));
+        assert_eq!(chess[Pos::new(0, 0)], None);
+        assert_eq!(chess[Pos::new(5, 0)], None);
+
+        // The squares the rook passes have to be empty as well, but may be attacked.
+        let chess = Chess::from_fen("1r2k3/8/8/8/8/8/8/RN3K2 w A - 0 1").unwrap();
+        assert!(!chess.moves().any(|m| m == queen_side));
+        let chess = Chess::from_fen("1r2k3/8/8/8/8/8/8/R4K2 w A - 0 1").unwrap();
+        assert!(chess.moves().any(|m| m == queen_side));
+    }
+
+    #[test]
+    fn test_castling_rights_lost_by_rook() {
+        let mut chess = Chess::default();
+        chess.set(Pos::new(7, 1), None);
+        chess.perform(Move::new(Pos::new(7, 0), Pos::new(7, 3)));
+        assert!(!chess.castling_rights[0].king_side);
+        assert!(chess.castling_rights[0].queen_side);
+    }
+
+    #[test]
+    fn test_no_castling_through_check() {
+        let mut chess = Chess::default();
+        chess.set(Pos::new(5, 0), None);
+        chess.set(Pos::new(6, 0), None);
+        // A black rook on an open f-file attacks the square the king has to pass.
+        chess.set(Pos::new(5, 1), None);
+        chess.set(Pos::new(5, 6), None);
+        chess.set(Pos::new(5, 5), Some(Piece::BLACK_ROOK));
+        assert!(!chess
+            .moves()
+            .any(|m| m == Move::new(Pos::new(4, 0), Pos::new(6, 0))));
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let mut chess = Chess::default();
+        chess.perform(Move::new(Pos::new(4, 1), Pos::new(4, 3)));
+        assert_eq!(chess.en_passant, Some(Pos::new(4, 2)));
+        chess.perform(Move::new(Pos::new(0, 6), Pos::new(0, 5)));
+        assert_eq!(chess.en_passant, None);
+        chess.perform(Move::new(Pos::new(4, 3), Pos::new(4, 4)));
+        chess.perform(Move::new(Pos::new(3, 6), Pos::new(3, 4)));
+        assert_eq!(chess.en_passant, Some(Pos::new(3, 5)));
+
+        // White can capture the black pawn that just passed its pawn.
+        let en_passant = Move::new(Pos::new(4, 4), Pos::new(3, 5));
+        assert!(chess.moves().any(|m| m == en_passant));
+        assert_eq!(chess.captured_pos(en_passant), Some(Pos::new(3, 4)));
+
+        chess.perform(en_passant);
+        assert_eq!(chess[Pos::new(3, 5)], Some(Piece::WHITE_PAWN));
+        assert_eq!(chess[Pos::new(3, 4)], None);
+        assert_eq!(chess[Pos::new(4, 4)], None);
+        assert_eq!(chess.en_passant, None);
+    }
+
+    #[test]
+    fn test_en_passant_expires() {
+        let mut chess = Chess::default();
+        chess.perform(Move::new(Pos::new(4, 1), Pos::new(4, 3)));
+        chess.perform(Move::new(Pos::new(0, 6), Pos::new(0, 5)));
+        chess.perform(Move::new(Pos::new(4, 3), Pos::new(4, 4)));
+        chess.perform(Move::new(Pos::new(3, 6), Pos::new(3, 4)));
+        // White waits a move, after which the en passant capture is no longer allowed.
+        chess.perform(Move::new(Pos::new(0, 1), Pos::new(0, 2)));
+        chess.perform(Move::new(Pos::new(0, 5), Pos::new(0, 4)));
+        assert!(!chess
+            .moves()
+            .any(|m| m == Move::new(Pos::new(4, 4), Pos::new(3, 5))));
+    }
+
+    #[test]
+    fn test_capture_to_graveyard() {
+        let mut chess = Chess::default();
+        chess.perform(Move::new(Pos::new(4, 1), Pos::new(4, 3)));
+        chess.perform(Move::new(Pos::new(3, 6), Pos::new(3, 4)));
+        chess.perform(Move::new(Pos::new(4, 3), Pos::new(3, 4)));
+        // The black pawn is put in the graveyard position closest to where it was captured.
+        assert_eq!(chess[Pos::new(-2, 4)], Some(Piece::BLACK_PAWN));
+        assert_eq!(chess.graveyards[1].graveyard[5][0], Some(Piece::BLACK_PAWN));
+
+        // When the column next to the board is full, the outer column is used, which holds the
+        // rest of the pieces of a player.
+        for pos in Chess::graveyard_of(Color::Black).take(10) {
+            chess.set(pos, Some(Piece::BLACK_PAWN));
+        }
+        let slot = chess.graveyard_slot(Piece::BLACK_PAWN, Pos::new(3, 3));
+        assert_eq!(slot, Some(Pos::new(-3, 3)));
+        for pos in Chess::graveyard_of(Color::Black) {
+            chess.set(pos, Some(Piece::BLACK_PAWN));
+        }
+        assert_eq!(
+            chess.graveyard_slot(Piece::BLACK_PAWN, Pos::new(3, 3)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_promotion() {
+        let mut chess = Chess::default();
+        chess.set(Pos::new(0, 6), Some(Piece::WHITE_PAWN));
+        chess.set(Pos::new(0, 7), None);
+        let promotions: Vec<Move> = chess
+            .moves()
+            .filter(|m| m.from == Pos::new(0, 6) && m.to == Pos::new(0, 7))
+            .collect();
+        assert_eq!(promotions.len(), 4);
+        assert!(promotions.iter().all(|m| m.promotion.is_some()));
+
+        // With a captured queen in the graveyard, the pawn is swapped for it.
+        let mut swapped = chess.clone();
+        swapped.set(Pos::new(9, 3), Some(Piece::WHITE_QUEEN));
+        swapped.perform(Move {
+            promotion: Some(Kind::Queen),
+            ..Move::new(Pos::new(0, 6), Pos::new(0, 7))
+        });
+        assert_eq!(swapped[Pos::new(0, 7)], Some(Piece::WHITE_QUEEN));
+        assert_eq!(swapped[Pos::new(9, 3)], None);
+        assert_eq!(swapped[Pos::new(9, 7)], Some(Piece::WHITE_PAWN));
+
+        // Without a spare piece, the pawn simply becomes the promoted piece.
+        chess.perform(Move {
+            promotion: Some(Kind::Knight),
+            ..Move::new(Pos::new(0, 6), Pos::new(0, 7))
+        });
+        assert_eq!(chess[Pos::new(0, 7)], Some(Piece::WHITE_KNIGHT));
+        assert!(Chess::graveyard_of(Color::White).all(|pos| chess[pos].is_none()));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let drawn = [
+            "8/8/4k3/8/8/4K3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3NK3/8/8 w - - 0 1",
+            // Both bishops stand on dark squares.
+            "8/8/4kb2/8/5B2/4K3/8/8 w - - 0 1",
+        ];
+        for fen in drawn {
+            let chess = Chess::from_fen(fen).unwrap();
+            assert!(chess.is_insufficient_material());
+            assert_eq!(
+                chess.outcome(),
+                Some(Outcome::Draw(Draw::InsufficientMaterial))
+            );
+        }
+        let playable = [
+            "8/8/4k3/8/8/3NKN2/8/8 w - - 0 1",
+            "8/8/4kb2/8/8/4KB2/8/8 w - - 0 1",
+            "8/8/4k3/8/8/4K3/4P3/8 w - - 0 1",
+        ];
+        for fen in playable {
+            assert!(!Chess::from_fen(fen).unwrap().is_insufficient_material());
+        }
+    }
+
+    #[test]
+    fn test_fifty_moves() {
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/P7/R3K3 w - - 98 70").unwrap();
+        chess.perform(Move::new(Pos::new(0, 0), Pos::new(1, 0)));
+        chess.perform(Move::new(Pos::new(4, 7), Pos::new(3, 7)));
+        assert_eq!(chess.halfmove_clock, 100);
+        assert_eq!(chess.fullmove_number, 71);
+        assert_eq!(chess.outcome(), Some(Outcome::Draw(Draw::FiftyMoves)));
+
+        // A pawn move resets the clock.
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/P7/R3K3 w - - 99 70").unwrap();
+        chess.perform(Move::new(Pos::new(0, 1), Pos::new(0, 2)));
+        assert_eq!(chess.halfmove_clock, 0);
+        assert_eq!(chess.outcome(), None);
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut chess = Chess::default();
+        let knight_moves = [
+            Move::new(Pos::new(6, 0), Pos::new(5, 2)),
+            Move::new(Pos::new(6, 7), Pos::new(5, 5)),
+            Move::new(Pos::new(5, 2), Pos::new(6, 0)),
+            Move::new(Pos::new(5, 5), Pos::new(6, 7)),
+        ];
+        for m in knight_moves {
+            chess.perform(m);
+        }
+        assert_eq!(chess.repetitions(), 2);
+        assert_eq!(chess.outcome(), None);
+        for m in knight_moves {
+            chess.perform(m);
+        }
+        assert_eq!(chess.repetitions(), 3);
+        assert_eq!(chess.outcome(), Some(Outcome::Draw(Draw::Repetition)));
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut chess = Chess::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        chess.set(Pos::new(9, 3), Some(Piece::WHITE_QUEEN));
+        let moves = [
+            // En passant.
+            Move::new(Pos::new(4, 4), Pos::new(3, 5)),
+            Move::new(Pos::new(4, 7), Pos::new(6, 7)),
+            // A capture that promotes to the captured queen.
+            Move {
+                promotion: Some(Kind::Queen),
+                ..Move::new(Pos::new(1, 6), Pos::new(0, 7))
+            },
+            Move::new(Pos::new(6, 7), Pos::new(7, 7)),
+            Move::new(Pos::new(4, 0), Pos::new(2, 0)),
+        ];
+        for m in moves {
+            let before = chess.clone();
+            chess.perform(m);
+            let after = chess.clone();
+            assert_eq!(chess.undo(), Some(m));
+            assert_eq!(chess, before);
+            chess = after;
+        }
+        for _ in moves {
+            chess.undo();
+        }
+        assert_eq!(chess.to_fen(), "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        assert_eq!(chess[Pos::new(9, 3)], Some(Piece::WHITE_QUEEN));
+        assert_eq!(chess.undo(), None);
+    }
+
+    #[test]
+    fn test_undo_moves() {
+        let mut chess = Chess::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+        chess.perform(Move::new(Pos::new(4, 4), Pos::new(3, 5)));
+        // The pawn moves back before the captured pawn leaves the graveyard.
+        assert_eq!(
+            chess.undo_moves(),
+            vec![
+                Move::new(Pos::new(3, 5), Pos::new(4, 4)),
+                Move::new(Pos::new(-2, 4), Pos::new(3, 4)),
+            ]
+        );
+
+        chess.perform(Move::new(Pos::new(4, 7), Pos::new(3, 7)));
+        chess.perform(Move::new(Pos::new(4, 0), Pos::new(6, 0)));
+        assert_eq!(
+            chess.undo_moves(),
+            vec![
+                Move::new(Pos::new(5, 0), Pos::new(7, 0)),
+                Move::new(Pos::new(6, 0), Pos::new(4, 0)),
+            ]
+        );
+    }
 }
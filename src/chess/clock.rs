@@ -0,0 +1,270 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+use bevy::prelude::Resource;
+
+use crate::chess::{chess::Color, computer::Budget};
+
+/// How many moves the time left on the clock has to last, when it is not known how many moves
+/// are left until the next time control.
+pub const MOVES_TO_GO: u32 = 30;
+
+/// The time a player gets back for every move they make.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Increment {
+    /// The time is added to the clock after every move.
+    Fischer(Duration),
+    /// The time the move took is added back to the clock, but never more than the delay.
+    Bronstein(Duration),
+}
+
+/// The time each player gets for the whole game, and the [`Increment`] for every move. It is
+/// written as the minutes of the base time and the seconds of the increment, separated by `+`
+/// for a Fischer increment or by `d` for a Bronstein delay, for example `5+3` or `15d10`. Without
+/// an increment, only the minutes are written.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Increment,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || format!("unknown time control \"{s}\", use for example 5+3 or 15d10");
+        let time = |text: &str, unit: f64| {
+            text.parse::<f64>()
+                .ok()
+                .filter(|amount| amount.is_finite() && *amount >= 0.0)
+                .map(|amount| Duration::from_secs_f64(amount * unit))
+                .ok_or_else(usage)
+        };
+        let (base, increment) = if let Some((base, seconds)) = s.split_once('+') {
+            (base, Increment::Fischer(time(seconds, 1.0)?))
+        } else if let Some((base, seconds)) = s.split_once('d') {
+            (base, Increment::Bronstein(time(seconds, 1.0)?))
+        } else {
+            (s, Increment::Fischer(Duration::ZERO))
+        };
+        let base = time(base, 60.0)?;
+        if base.is_zero() {
+            return Err(usage());
+        }
+        Ok(TimeControl { base, increment })
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.base.as_secs_f64() / 60.0)?;
+        match self.increment {
+            Increment::Fischer(time) if time.is_zero() => Ok(()),
+            Increment::Fischer(time) => write!(f, "+{}", time.as_secs_f64()),
+            Increment::Bronstein(time) => write!(f, "d{}", time.as_secs_f64()),
+        }
+    }
+}
+
+/// The clocks of both players. Only the clock of the player to move runs, and it is paused while
+/// the move is made on the board, so the time the magnet takes is not counted. Once the time of
+/// a player has run out, their flag falls and they lose the game.
+///
+/// The clock does not measure time itself, it is told how much time has passed with
+/// [`Clock::tick`].
+#[derive(Resource, Clone, Debug, Eq, PartialEq)]
+pub struct Clock {
+    pub control: TimeControl,
+    /// The time left of each player, stored on the same indices as [`Chess::kings`].
+    ///
+    /// [`Chess::kings`]: crate::chess::chess::Chess::kings
+    left: [Duration; 2],
+    /// The player whose move it is, whose clock runs unless it is paused.
+    turn: Color,
+    running: bool,
+    /// How long the current move has taken so far.
+    spent: Duration,
+    /// The player whose time has run out.
+    flag: Option<Color>,
+}
+
+impl Clock {
+    /// Creates the clocks of a game with the given time control. Both players get the base time,
+    /// and the clocks are paused until [`Clock::start`] is called.
+    pub fn new(control: TimeControl) -> Self {
+        Clock {
+            control,
+            left: [control.base; 2],
+            turn: Color::White,
+            running: false,
+            spent: Duration::ZERO,
+            flag: None,
+        }
+    }
+
+    /// The time the given player has left.
+    pub fn left(&self, color: Color) -> Duration {
+        self.left[color.king_index()]
+    }
+
+    /// The player whose time has run out, if any.
+    pub fn flag(&self) -> Option<Color> {
+        self.flag
+    }
+
+    /// Whether the clock of a player is running.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Starts the clock of the given player for a new move.
+    pub fn start(&mut self, color: Color) {
+        self.turn = color;
+        self.spent = Duration::ZERO;
+        self.running = self.flag.is_none();
+    }
+
+    /// Pauses the running clock, for example while a move is made on the board. The time the
+    /// move took so far is kept for [`Clock::finish_move`].
+    pub fn pause(&mut self) {
+        self.running = false;
+    }
+
+    /// Gives the player whose move it was their [`Increment`] for the move they made.
+    pub fn finish_move(&mut self) {
+        self.running = false;
+        if self.flag.is_some() {
+            return;
+        }
+        let increment = match self.control.increment {
+            Increment::Fischer(time) => time,
+            Increment::Bronstein(delay) => delay.min(self.spent),
+        };
+        self.left[self.turn.king_index()] += increment;
+    }
+
+    /// Lets the given amount of time pass on the running clock. Returns the player whose flag
+    /// fell because of it.
+    pub fn tick(&mut self, elapsed: Duration) -> Option<Color> {
+        if !self.running {
+            return None;
+        }
+        let left = &mut self.left[self.turn.king_index()];
+        *left = left.saturating_sub(elapsed);
+        self.spent += elapsed;
+        if left.is_zero() {
+            self.running = false;
+            self.flag = Some(self.turn);
+            return self.flag;
+        }
+        None
+    }
+
+    /// How long the computer may think about its move with the time the given player has left,
+    /// see [`time_budget`].
+    pub fn budget(&self, color: Color) -> Budget {
+        let increment = match self.control.increment {
+            Increment::Fischer(time) | Increment::Bronstein(time) => time,
+        };
+        time_budget(self.left(color), increment, None)
+    }
+}
+
+/// Writes the time left of both players, for example `White 4:59 - Black 5:00`.
+impl Display for Clock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let time = |color: Color| {
+            // The time is rounded up, so the flag falls when the clock shows 0:00.
+            let seconds = self.left(color).as_secs() + (self.left(color).subsec_nanos() > 0) as u64;
+            format!("{color} {}:{:02}", seconds / 60, seconds % 60)
+        };
+        write!(f, "{} - {}", time(Color::White), time(Color::Black))
+    }
+}
+
+/// How long to think about a move with the given time left on the clock: an equal part of the
+/// time for each of the moves to go, plus half the increment. Without the number of moves to go,
+/// the time has to last [`MOVES_TO_GO`] moves. A move never takes more than half the time left.
+pub fn time_budget(left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Budget {
+    let moves_to_go = moves_to_go.unwrap_or(MOVES_TO_GO).max(1);
+    Budget::Time((left / moves_to_go + increment / 2).min(left / 2))
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::clock::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_time_control() {
+        let control: TimeControl = "5+3".parse().unwrap();
+        assert_eq!(control.base, 300 * SECOND);
+        assert_eq!(control.increment, Increment::Fischer(3 * SECOND));
+        assert_eq!(control.to_string(), "5+3");
+        let control: TimeControl = "0.5d2".parse().unwrap();
+        assert_eq!(control.base, 30 * SECOND);
+        assert_eq!(control.increment, Increment::Bronstein(2 * SECOND));
+        assert_eq!(control.to_string(), "0.5d2");
+        assert_eq!("10".parse::<TimeControl>().unwrap().to_string(), "10");
+
+        assert!("0+2".parse::<TimeControl>().is_err());
+        assert!("5+x".parse::<TimeControl>().is_err());
+        assert!("-5".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_fischer() {
+        let mut clock = Clock::new("1+2".parse().unwrap());
+        // The clock does not run before it is started, nor while it is paused.
+        assert_eq!(clock.tick(10 * SECOND), None);
+        clock.start(Color::White);
+        clock.tick(10 * SECOND);
+        clock.pause();
+        clock.tick(10 * SECOND);
+        clock.finish_move();
+        assert_eq!(clock.left(Color::White), 52 * SECOND);
+        assert_eq!(clock.left(Color::Black), 60 * SECOND);
+        assert_eq!(clock.to_string(), "White 0:52 - Black 1:00");
+
+        clock.start(Color::Black);
+        assert_eq!(clock.tick(59 * SECOND), None);
+        assert_eq!(clock.tick(2 * SECOND), Some(Color::Black));
+        assert_eq!(clock.flag(), Some(Color::Black));
+        assert_eq!(clock.left(Color::Black), Duration::ZERO);
+        assert!(!clock.is_running());
+        // A fallen flag stays down.
+        clock.finish_move();
+        clock.start(Color::Black);
+        assert_eq!(clock.left(Color::Black), Duration::ZERO);
+        assert!(!clock.is_running());
+    }
+
+    #[test]
+    fn test_bronstein() {
+        let mut clock = Clock::new("1d5".parse().unwrap());
+        clock.start(Color::White);
+        clock.tick(3 * SECOND);
+        clock.finish_move();
+        assert_eq!(clock.left(Color::White), 60 * SECOND);
+        clock.start(Color::Black);
+        clock.tick(8 * SECOND);
+        clock.finish_move();
+        assert_eq!(clock.left(Color::Black), 57 * SECOND);
+    }
+
+    #[test]
+    fn test_budget() {
+        let clock = Clock::new("5+2".parse().unwrap());
+        assert_eq!(clock.budget(Color::White), Budget::Time(11 * SECOND));
+        // Little time is left, so the move may take half of it.
+        assert_eq!(
+            time_budget(2 * SECOND, 10 * SECOND, Some(5)),
+            Budget::Time(SECOND)
+        );
+    }
+}
@@ -1,59 +1,466 @@
-use bevy::prelude::{EventReader, EventWriter, Plugin, Res, ResMut};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{EventReader, EventWriter, IntoSystemConfig, Plugin, Res, ResMut, Resource},
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
 
 use crate::{
     chess::{chess::Chess, chess::Move},
-    controller::controller::{ComputerTurnEvent, CurrentMove, MoveEvent, Player, PlayerTurn},
+    controller::controller::{
+        ComputerTurnEvent, DrawOffer, GameOverEvent, MoveEvent, NewMove, Player, PlayerTurn,
+    },
 };
 
 use super::{
-    chess::{Color, Outcome},
-    BoardState,
+    chess::Color,
+    clock::Clock,
+    evaluation::{Evaluator, Positional},
+    ordering::{self, MoveOrder},
+    polyglot::OpeningBook,
+    score::Score,
+    transposition::{Bound, Entry, TranspositionTable},
+    BoardState, GameHistory,
 };
+/// The deepest iteration [`search`] will start.
+pub const MAX_DEPTH: u8 = 64;
+/// How many moves of both players deep [`quiescence`] searches all moves of a checked player.
+/// Deeper than that a checked player is scored like any other, so a long series of checks can
+/// not keep the search going.
+const QUIESCENCE_CHECK_PLIES: u8 = 8;
+/// How many moves of both players without a capture or a pawn move it takes before the computer
+/// offers a draw, and how long it waits before offering another one.
+const DRAW_OFFER_PLIES: usize = 20;
+
 /// Move with the highest score acording to the minimax algorithm.
 pub struct BestMove {
     pub m: Option<Move>,
-    score: i16,
+    pub score: Score,
+    /// How many moves deep the move was searched.
+    pub depth: u8,
+}
+
+/// How much the computer player may search for a move: for a while, a number of positions, or a
+/// number of moves deep.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Budget {
+    Time(Duration),
+    Nodes(u64),
+    Depth(u8),
+}
+
+/// Counts the positions a search visits, and stops the search once its [`Budget`] is used up.
+pub struct Limit {
+    budget: Option<Budget>,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    /// Stops the search from the outside once it is set, whatever is left of the budget.
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Limit {
+    pub fn new(budget: Budget) -> Self {
+        Limit {
+            budget: Some(budget),
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            stop: None,
+        }
+    }
+
+    /// Lets the search also be stopped by setting the given flag, for example from another thread.
+    pub fn with_stop(self, stop: Arc<AtomicBool>) -> Self {
+        Limit {
+            stop: Some(stop),
+            ..self
+        }
+    }
+
+    /// A limit that never stops the search, which then only ends at the given depth.
+    pub fn unlimited() -> Self {
+        Limit {
+            budget: None,
+            ..Limit::new(Budget::Nodes(0))
+        }
+    }
+
+    /// The number of positions visited so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The deepest iteration [`search`] may start with this limit.
+    pub fn max_depth(&self) -> u8 {
+        match self.budget {
+            Some(Budget::Depth(depth)) => depth.min(MAX_DEPTH),
+            _ => MAX_DEPTH,
+        }
+    }
+
+    /// Whether the budget is used up, in which case the result of the search has to be ignored.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Counts a visited position, and tells whether the search has to stop.
+    fn visit(&mut self) -> bool {
+        // Reading the clock and the stop flag takes a while, so it is only done for the first
+        // position and then once every 1024 positions.
+        let check = self.nodes & 1023 == 0;
+        self.nodes += 1;
+        if !self.stopped {
+            let stopped = match &self.stop {
+                Some(stop) => check && stop.load(Ordering::Relaxed),
+                None => false,
+            };
+            self.stopped = stopped
+                || match self.budget {
+                    None | Some(Budget::Depth(_)) => false,
+                    Some(Budget::Nodes(nodes)) => self.nodes > nodes,
+                    Some(Budget::Time(time)) => check && self.start.elapsed() >= time,
+                };
+        }
+        self.stopped
+    }
+}
+
+/// The strength of the computer player, which is set by the [`Budget`] it gets for each move.
+#[derive(Resource, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn budget(&self) -> Budget {
+        match self {
+            Difficulty::Easy => Budget::Nodes(2_000),
+            Difficulty::Medium => Budget::Time(Duration::from_millis(500)),
+            Difficulty::Hard => Budget::Time(Duration::from_secs(3)),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    /// Reads a difficulty from its name, like `easy`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!(
+                "unknown difficulty \"{s}\", use easy, medium or hard"
+            )),
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+/// Finds the moves of the computer player. [`Thinking`] asks it for its move in the background, so
+/// it may take its time.
+pub trait ComputerPlayer: Send + Sync {
+    /// Returns the best move in the given position it finds within the budget. Once `stop` is set
+    /// it has to return as soon as it can, the move will not be played anymore.
+    fn best_move(&self, chess: &Chess, budget: Budget, stop: Arc<AtomicBool>) -> BestMove;
+}
+
+/// The chess computer of this crate, which finds its moves with [`search`], or plays them from
+/// an [`OpeningBook`] while the position is in it.
+#[derive(Default)]
+pub struct Minimax {
+    /// The [`TranspositionTable`] is kept between the searches, so the positions searched for
+    /// the previous move do not have to be searched again.
+    table: Mutex<TranspositionTable>,
+    book: Option<OpeningBook>,
+}
+
+impl Minimax {
+    /// Lets the computer play the moves of the book in the positions it knows, instead of
+    /// searching.
+    pub fn with_book(self, book: OpeningBook) -> Self {
+        Minimax {
+            book: Some(book),
+            ..self
+        }
+    }
 }
+
+impl ComputerPlayer for Minimax {
+    /// A move from the book is scored by the evaluation of the position after it, as it is not
+    /// searched.
+    fn best_move(&self, chess: &Chess, budget: Budget, stop: Arc<AtomicBool>) -> BestMove {
+        if let Some(m) = self.book.as_ref().and_then(|book| book.probe(chess)) {
+            let mut after = chess.clone();
+            after.perform(m);
+            return BestMove {
+                m: Some(m),
+                score: Score::evaluation(evaluate::<Positional>(&after)),
+                depth: 0,
+            };
+        }
+        let limit = Limit::new(budget).with_stop(stop);
+        search::<Positional>(&mut chess.clone(), &mut self.table.lock().unwrap(), limit)
+    }
+}
+
 /// Plugin that runs the system for the bevy app.
 pub struct ChessComputerPlugin;
 
 impl Plugin for ChessComputerPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_system(return_move);
+        app.init_resource::<Thinking>()
+            .init_resource::<Difficulty>()
+            .add_system(start_thinking)
+            .add_system(return_move.after(start_thinking));
     }
 }
 
-/// When a new [`ComputerTurnEvent`] is registered this function will generate a new move,
-/// if it's the computer player's turn. When a new move had been found, this move will be
-/// stored in [`CurrentMove`]
+/// The search for the move of the computer player. The search runs on the
+/// [`AsyncComputeTaskPool`], so the simulation keeps running while the computer is thinking.
+/// By default the [`Minimax`] computer plays, another [`ComputerPlayer`] can be set with
+/// [`Thinking::new`].
+#[derive(Resource)]
+pub struct Thinking {
+    task: Option<Task<BestMove>>,
+    /// The [`Chess::position_key`] of the position that is searched.
+    key: u64,
+    /// Stops the running search once it is set.
+    stop: Arc<AtomicBool>,
+    player: Arc<dyn ComputerPlayer>,
+    /// The score of the last move that was found.
+    score: Option<Score>,
+}
+
+impl Default for Thinking {
+    fn default() -> Self {
+        Thinking::new(Minimax::default())
+    }
+}
+
+impl Thinking {
+    /// Lets the given player find the moves of the computer.
+    pub fn new(player: impl ComputerPlayer + 'static) -> Self {
+        Thinking {
+            task: None,
+            key: 0,
+            stop: Arc::default(),
+            player: Arc::new(player),
+            score: None,
+        }
+    }
+
+    /// Whether a search is running.
+    pub fn is_thinking(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Starts searching the best move in the given position within the budget, in the
+    /// background. A search that is still running is stopped first.
+    pub fn start(&mut self, chess: &Chess, budget: Budget) {
+        self.cancel();
+        let chess = chess.clone();
+        self.key = chess.position_key();
+        self.stop = Arc::new(AtomicBool::new(false));
+        let stop = self.stop.clone();
+        let player = self.player.clone();
+        self.task = Some(
+            AsyncComputeTaskPool::get()
+                .spawn(async move { player.best_move(&chess, budget, stop) }),
+        );
+    }
+
+    /// Stops the running search, for example because the position has changed. The move it was
+    /// looking for will not be played.
+    pub fn cancel(&mut self) {
+        if self.task.take().is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the computer playing with the given color accepts a draw, which it does when the
+    /// last move it found does not lead to a better position for it.
+    pub fn accepts_draw(&self, color: Color) -> bool {
+        match (self.score, color) {
+            (Some(score), Color::White) => score <= Score::DRAW,
+            (Some(score), Color::Black) => score >= Score::DRAW,
+            (None, _) => false,
+        }
+    }
+
+    /// Returns the result of the search once it has finished.
+    fn poll(&mut self) -> Option<BestMove> {
+        let task = self.task.as_mut()?;
+        let best_move = future::block_on(future::poll_once(task))?;
+        self.task = None;
+        Some(best_move)
+    }
+}
+
+/// Whose turn it is, and how long the computer may search for its move: as long as the
+/// [`Difficulty`] allows, or by the time it has left when the game is played with a [`Clock`].
+#[derive(SystemParam)]
+pub struct ComputerBudget<'w> {
+    player_turn: Res<'w, PlayerTurn>,
+    difficulty: Res<'w, Difficulty>,
+    clock: Option<Res<'w, Clock>>,
+}
+
+impl ComputerBudget<'_> {
+    /// Returns the budget for the move of the computer in the given position, or `None` when it
+    /// is not the computer player's turn.
+    fn budget(&self, chess: &Chess) -> Option<Budget> {
+        if self.player_turn.turn != Player::Computer {
+            return None;
+        }
+        Some(match &self.clock {
+            Some(clock) => clock.budget(chess.turn),
+            None => self.difficulty.budget(),
+        })
+    }
+}
+
+/// When a new [`ComputerTurnEvent`] is registered this function will start looking for a new
+/// move in the background, if it's the computer player's turn, within the [`ComputerBudget`].
+pub fn start_thinking(
+    mut computer_turn: EventReader<ComputerTurnEvent>,
+    boardstate: Res<BoardState>,
+    mut thinking: ResMut<Thinking>,
+    budget: ComputerBudget,
+) {
+    for _event in computer_turn.iter() {
+        if let Some(budget) = budget.budget(&boardstate.chess) {
+            thinking.start(&boardstate.chess, budget);
+        }
+    }
+}
+
+/// Checks every frame whether the computer has finished [`Thinking`]. When a new move had been
+/// found, this move will be stored in [`CurrentMove`]
 /// and the function will send a [`MoveEvent`] triggering [`update_path`].
-/// When there are no more moves, the game has ended and
-/// the outcome will be printed.
+/// When there are no more moves, the game has ended and a [`GameOverEvent`] is sent.
+/// A move for a position that is no longer on the board is ignored, and the search starts over
+/// for the position on the board when it is still the computer player's turn.
+///
+/// When the computer finds that the game is a draw with the best moves of both players, and
+/// nothing has been captured for a while, it offers a draw along with its move, see
+/// [`DrawOffer`].
 ///
+/// [`CurrentMove`]: crate::controller::controller::CurrentMove
 /// [`update_path`]: crate::controller::controller::update_path
 pub fn return_move(
-    mut computer_turn: EventReader<ComputerTurnEvent>,
+    mut thinking: ResMut<Thinking>,
     boardstate: Res<BoardState>,
-    player_turn: Res<PlayerTurn>,
-    mut new_move: EventWriter<MoveEvent>,
-    mut current_move: ResMut<CurrentMove>,
+    history: Res<GameHistory>,
+    budget: ComputerBudget,
+    mut new_move: NewMove,
+    mut draw_offer: ResMut<DrawOffer>,
+    mut game_over: EventWriter<GameOverEvent>,
 ) {
-    for _event in computer_turn.iter() {
-        if player_turn.turn == Player::Computer {
-            let chess = boardstate.chess;
-            let best_move = minimax(&chess, 3, i16::MIN, i16::MAX);
-            if best_move.m.is_some() {
-                current_move.current_move = best_move.m.unwrap();
-                new_move.send(MoveEvent);
-            } else {
-                match chess.outcome().unwrap() {
-                    Outcome::Winner(color) => println!("{color} wins!"),
-                    Outcome::Stalemate => println!("it's a stalemate!"),
-                }
+    let best_move = match thinking.poll() {
+        Some(best_move) if thinking.key == boardstate.chess.position_key() => best_move,
+        Some(_) => {
+            if let Some(budget) = budget.budget(&boardstate.chess) {
+                thinking.start(&boardstate.chess, budget);
             }
+            return;
+        }
+        None => return,
+    };
+    if let Some(m) = best_move.m {
+        thinking.score = Some(best_move.score);
+        let moves = history.game.moves.len();
+        if best_move.score == Score::DRAW
+            && boardstate.chess.halfmove_clock as usize >= DRAW_OFFER_PLIES
+            && !matches!(draw_offer.moves, Some(offered) if moves < offered + DRAW_OFFER_PLIES)
+        {
+            draw_offer.moves = Some(moves);
+            println!("The computer offers a draw, press D to accept it.");
+        }
+        new_move.current_move.current_move = m;
+        new_move.event.send(MoveEvent);
+    } else if let Some(outcome) = boardstate.chess.outcome() {
+        game_over.send(GameOverEvent { outcome });
+    }
+}
+
+/// Searches the best move with iterative deepening: the position is searched one move deep, then
+/// two moves deep, and so on until the budget is used up. The move of the deepest search that
+/// was completed is returned. Because every search fills the [`TranspositionTable`] with the
+/// best moves of the positions, the next search tries those first and can cut off more branches,
+/// so the shallow searches cost little extra time.
+///
+/// The first search, one move deep, is always completed, so a move is found with any limit.
+///
+/// The [`MoveOrder`] learns from every search which moves cause cut offs, so it is shared by all
+/// searches.
+pub fn search<E: Evaluator>(
+    chess: &mut Chess,
+    table: &mut TranspositionTable,
+    mut limit: Limit,
+) -> BestMove {
+    let mut order = MoveOrder::default();
+    let mut best_move = minimax::<E>(
+        chess,
+        table,
+        &mut order,
+        &mut Limit::unlimited(),
+        1,
+        -Score::INFINITY,
+        Score::INFINITY,
+    );
+    for depth in 2..=limit.max_depth() {
+        // A game that is over does not get any different by searching deeper. Neither does a
+        // forced mate within the depth that was searched: a shorter mate would have been found
+        // already, and so would a longer defence against it. A mate further away was found by
+        // the quiescence search or in the transposition table, and a deeper search may find a
+        // shorter one.
+        let searched = depth as i16 - 1;
+        if best_move.m.is_none()
+            || matches!(best_move.score.mate_plies(), Some(plies) if plies <= searched)
+        {
+            break;
+        }
+        let deeper = minimax::<E>(
+            chess,
+            table,
+            &mut order,
+            &mut limit,
+            depth,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        if limit.is_stopped() {
+            break;
         }
+        best_move = deeper;
     }
+    best_move
 }
+
 /// Function for determining the next move of the computer player. For the Black player
 /// the score has to be Minimized, and maximized for the white player
 /// It takes the current [`BoardState`] and checks what is the best move
@@ -62,20 +469,94 @@ pub fn return_move(
 /// The depth states how many moves deep the algorithm searches for the best move.
 /// It's a depth first search recursive function, that first checks a series of moves until the given depth,
 /// calculates a score for the state at the end of these moves. Then it moves on to the next series of possible moves.
-pub fn minimax(chess: &Chess, depth: u8, mut alpha: i16, mut beta: i16) -> BestMove {
-    if (depth == 0) || chess.outcome().is_some() {
-        BestMove {
+/// The moves are performed on the given board and taken back afterwards, so no copies of the
+/// board are needed.
+/// The result of every searched position is stored in the [`TranspositionTable`]. A position
+/// that was already searched deep enough is not searched again, and otherwise the best move of
+/// the earlier search is tried first. The other moves are sorted by the [`MoveOrder`], the most
+/// promising first, which lets alpha-beta pruning cut off more branches.
+/// At the given depth the search goes on with a [`quiescence`] search, so the score is not taken
+/// in the middle of an exchange of pieces. The positions it ends in are scored by the
+/// [`Evaluator`]. A mate found after a move is one move further away from this position, so the
+/// [`Score`] of each move is its [`Score::parent`], and the shortest mate scores highest.
+/// Once the [`Limit`] stops the search, it returns right away without a move.
+pub fn minimax<E: Evaluator>(
+    chess: &mut Chess,
+    table: &mut TranspositionTable,
+    order: &mut MoveOrder,
+    limit: &mut Limit,
+    depth: u8,
+    mut alpha: Score,
+    mut beta: Score,
+) -> BestMove {
+    if depth == 0 {
+        return BestMove {
+            m: None,
+            score: quiescence::<E>(chess, order, limit, 0, alpha, beta),
+            depth,
+        };
+    }
+    if limit.visit() {
+        return BestMove {
             m: None,
-            score: chess.evaluate(),
+            score: Score::DRAW,
+            depth,
+        };
+    }
+    if let Some(outcome) = chess.outcome() {
+        return BestMove {
+            m: None,
+            score: outcome.value(),
+            depth,
+        };
+    }
+
+    let key = chess.position_key();
+    let stored = table.get(key);
+    if let Some(entry) = stored.filter(|entry| entry.depth >= depth) {
+        let cut_off = match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => entry.score >= beta,
+            Bound::Upper => entry.score <= alpha,
+        };
+        if cut_off {
+            return BestMove {
+                m: entry.best,
+                score: entry.score,
+                depth,
+            };
         }
-    } else if chess.turn == Color::White {
-        let mut best_move = None;
-        let mut best_score = i16::MIN;
+    }
+    let (first_alpha, first_beta) = (alpha, beta);
+
+    let mut moves: Vec<Move> = chess.moves().collect();
+    order.sort(chess, &mut moves, stored.and_then(|entry| entry.best));
 
-        for m in chess.moves() {
-            let mut copy = *chess;
-            copy.perform(m);
-            let score = minimax(&copy, depth - 1, alpha, beta).score;
+    let mut best_move = None;
+    let best_score = if chess.turn == Color::White {
+        let mut best_score = -Score::INFINITY;
+
+        for m in moves {
+            chess.perform(m);
+            let score = minimax::<E>(
+                chess,
+                table,
+                order,
+                limit,
+                depth - 1,
+                alpha.child(),
+                beta.child(),
+            )
+            .score
+            .parent();
+            chess.undo();
+            if limit.is_stopped() {
+                return BestMove {
+                    m: None,
+                    score: Score::DRAW,
+                    depth,
+                };
+            }
             if score > best_score || best_move.is_none() {
                 best_score = score;
                 best_move = Some(m);
@@ -83,23 +564,36 @@ pub fn minimax(chess: &Chess, depth: u8, mut alpha: i16, mut beta: i16) -> BestM
                     alpha = score;
                 }
                 if alpha >= beta {
+                    order.cut_off(chess, m, depth);
                     break;
                 }
             }
         }
-
-        BestMove {
-            m: Some(best_move.unwrap()),
-            score: best_score,
-        }
+        best_score
     } else {
-        let mut best_move = None;
-        let mut best_score = i16::MAX;
+        let mut best_score = Score::INFINITY;
 
-        for m in chess.moves() {
-            let mut copy = *chess;
-            copy.perform(m);
-            let score = minimax(&copy, depth - 1, alpha, beta).score;
+        for m in moves {
+            chess.perform(m);
+            let score = minimax::<E>(
+                chess,
+                table,
+                order,
+                limit,
+                depth - 1,
+                alpha.child(),
+                beta.child(),
+            )
+            .score
+            .parent();
+            chess.undo();
+            if limit.is_stopped() {
+                return BestMove {
+                    m: None,
+                    score: Score::DRAW,
+                    depth,
+                };
+            }
             if score < best_score || best_move.is_none() {
                 best_score = score;
                 best_move = Some(m);
@@ -107,15 +601,126 @@ pub fn minimax(chess: &Chess, depth: u8, mut alpha: i16, mut beta: i16) -> BestM
                     beta = score;
                 }
                 if alpha >= beta {
+                    order.cut_off(chess, m, depth);
                     break;
                 }
             }
         }
+        best_score
+    };
+
+    table.insert(Entry {
+        key,
+        depth,
+        bound: bound(best_score, first_alpha, first_beta),
+        score: best_score,
+        best: best_move,
+    });
+    BestMove {
+        m: Some(best_move.unwrap()),
+        score: best_score,
+        depth,
+    }
+}
 
-        BestMove {
-            m: Some(best_move.unwrap()),
-            score: best_score,
+/// Searches only the captures and promotions of a position, until a position is reached in which
+/// the player to move has none that improve its score. That score is returned, so a position is
+/// never scored right after a capture that can be answered with a recapture.
+///
+/// The player to move does not have to capture, so its score is at least the score the
+/// [`Evaluator`] gives the position as it is. A player that is checked has no such choice, so then all moves are
+/// searched, up to [`QUIESCENCE_CHECK_PLIES`] moves after the start of the quiescence search,
+/// which `ply` counts. The outcomes and evaluations follow the variant of the game.
+pub fn quiescence<E: Evaluator>(
+    chess: &mut Chess,
+    order: &MoveOrder,
+    limit: &mut Limit,
+    ply: u8,
+    mut alpha: Score,
+    mut beta: Score,
+) -> Score {
+    if limit.visit() {
+        return Score::DRAW;
+    }
+    let variant = chess.rules.variant();
+    let mut moves: Vec<Move> = chess.moves().collect();
+    // Captures reset the counter of the fifty move rule and can never repeat a position, so
+    // only the outcomes that can follow a capture are checked.
+    if moves.is_empty()
+        || variant.is_insufficient_material(chess)
+        || variant.outcome(chess).is_some()
+    {
+        if let Some(outcome) = chess.outcome() {
+            return outcome.value();
+        }
+    }
+    let checked =
+        ply < QUIESCENCE_CHECK_PLIES && variant.royal_king() && chess.is_checked(chess.turn);
+    moves.retain(|m| checked || !ordering::is_quiet(chess, *m));
+    order.sort(chess, &mut moves, None);
+
+    if chess.turn == Color::White {
+        let mut best_score = if checked {
+            -Score::INFINITY
+        } else {
+            Score::evaluation(evaluate::<E>(chess))
+        };
+        alpha = alpha.max(best_score);
+        for m in moves {
+            if alpha >= beta {
+                break;
+            }
+            chess.perform(m);
+            let score =
+                quiescence::<E>(chess, order, limit, ply + 1, alpha.child(), beta.child()).parent();
+            chess.undo();
+            if limit.is_stopped() {
+                return Score::DRAW;
+            }
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+        }
+        best_score
+    } else {
+        let mut best_score = if checked {
+            Score::INFINITY
+        } else {
+            Score::evaluation(evaluate::<E>(chess))
+        };
+        beta = beta.min(best_score);
+        for m in moves {
+            if alpha >= beta {
+                break;
+            }
+            chess.perform(m);
+            let score =
+                quiescence::<E>(chess, order, limit, ply + 1, alpha.child(), beta.child()).parent();
+            chess.undo();
+            if limit.is_stopped() {
+                return Score::DRAW;
+            }
+            best_score = best_score.min(score);
+            beta = beta.min(score);
         }
+        best_score
+    }
+}
+
+/// Scores a position with the [`Evaluator`], changed by the variant of the game to what is worth
+/// striving for in it.
+fn evaluate<E: Evaluator>(chess: &Chess) -> i16 {
+    chess.rules.variant().evaluate(chess, E::evaluate(chess))
+}
+
+/// Tells how the score of a position relates to its real score, given the alpha and beta the
+/// position was searched with. A score outside of those was cut off, so it is only a bound.
+fn bound(score: Score, alpha: Score, beta: Score) -> Bound {
+    if score <= alpha {
+        Bound::Upper
+    } else if score >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
     }
 }
 
@@ -124,10 +729,15 @@ pub fn minimax(chess: &Chess, depth: u8, mut alpha: i16, mut beta: i16) -> BestM
 mod tests {
     use crate::chess::{
         chess::Chess,
-        chess::{Color, Move},
-        computer::minimax,
+        chess::{Color, Move, Outcome},
+        computer::*,
+        evaluation::Material,
+        fen::START_FEN,
+        polyglot::{self, BookChoice},
         pos::Pos,
+        transposition::{Bound, TranspositionTable},
     };
+    use bevy::tasks::TaskPool;
 
     #[test]
     fn test_minimax() {
@@ -137,28 +747,294 @@ mod tests {
         chess.perform(Move {
             from: Pos::new(5, 1),
             to: Pos::new(5, 2),
+            promotion: None,
         });
         chess.perform(Move {
             from: Pos::new(4, 6),
             to: Pos::new(4, 5),
+            promotion: None,
         });
         chess.perform(Move {
             from: Pos::new(6, 1),
             to: Pos::new(6, 3),
+            promotion: None,
         });
         // The black player can checkmate white by performing:
         // chess.perform(Move::new(Pos::new(3, 7), Pos::new(7, 3)));
         chess.turn = Color::Black;
         // Call the minimax function with the known board state
-        let best_move = minimax(&chess, 2, i16::MIN, i16::MAX);
+        let before = chess.clone();
+        let mut table = TranspositionTable::default();
+        let best_move = minimax::<Positional>(
+            &mut chess,
+            &mut table,
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            2,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
         // Assert that the best move and score match the expected values
         // In this example, we expect the best move to be the one that puts white in a checkmate
         assert_eq!(
             best_move.m.unwrap(),
             Move {
                 from: Pos::new(3, 7),
-                to: Pos::new(7, 3)
+                to: Pos::new(7, 3),
+                promotion: None,
+            }
+        );
+        // All moves that were tried out have been taken back.
+        assert_eq!(chess, before);
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut chess = Chess::from_fen(START_FEN).unwrap();
+        let mut table = TranspositionTable::default();
+        let first = minimax::<Positional>(
+            &mut chess,
+            &mut table,
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            3,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        let entry = table.get(chess.position_key()).unwrap();
+        assert_eq!((entry.depth, entry.bound), (3, Bound::Exact));
+        assert_eq!(entry.best, first.m);
+
+        // The second search finds the result of the first one in the table.
+        let second = minimax::<Positional>(
+            &mut chess,
+            &mut table,
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            3,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        assert_eq!((second.m, second.score), (first.m, first.score));
+
+        // The table does not change the outcome of the search.
+        let mut without = TranspositionTable::new(1);
+        let searched = minimax::<Positional>(
+            &mut chess,
+            &mut without,
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            3,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        assert_eq!(searched.score, first.score);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut chess = Chess::default();
+        let mut table = TranspositionTable::default();
+        let best_move =
+            search::<Positional>(&mut chess, &mut table, Limit::new(Budget::Nodes(5_000)));
+        assert!(best_move.depth >= 2);
+        assert!(chess.moves().any(|m| Some(m) == best_move.m));
+        assert_eq!(chess, Chess::default());
+
+        // A depth budget lets the search go exactly as deep.
+        let best_move = search::<Positional>(&mut chess, &mut table, Limit::new(Budget::Depth(3)));
+        assert_eq!(best_move.depth, 3);
+
+        // Even without a budget the first search is completed.
+        let best_move = search::<Positional>(&mut chess, &mut table, Limit::new(Budget::Nodes(0)));
+        assert_eq!(best_move.depth, 1);
+        assert!(best_move.m.is_some());
+
+        // A time budget is kept to, give or take the time it takes to notice it is used up.
+        let start = Instant::now();
+        let limit = Limit::new(Budget::Time(Duration::from_millis(100)));
+        search::<Positional>(&mut chess, &mut table, limit);
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // A search that is stopped from the outside ends right away.
+        let stop = Arc::new(AtomicBool::new(true));
+        let limit = Limit::new(Budget::Time(Duration::from_secs(60))).with_stop(stop);
+        let best_move = search::<Positional>(&mut chess, &mut table, limit);
+        assert_eq!(best_move.depth, 1);
+    }
+
+    #[test]
+    fn test_thinking() {
+        AsyncComputeTaskPool::init(TaskPool::new);
+        let chess = Chess::default();
+        let mut thinking = Thinking::default();
+        thinking.start(&chess, Budget::Nodes(1_000));
+        assert!(thinking.is_thinking());
+        let best_move = loop {
+            if let Some(best_move) = thinking.poll() {
+                break best_move;
             }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert!(!thinking.is_thinking());
+        assert!(chess.moves().any(|m| Some(m) == best_move.m));
+
+        // The move of a search that was stopped never arrives.
+        thinking.start(&chess, Budget::Time(Duration::from_secs(60)));
+        thinking.cancel();
+        assert!(!thinking.is_thinking());
+        assert!(thinking.poll().is_none());
+    }
+
+    #[test]
+    fn test_opening_book() {
+        // A book with only 1. e4 in the start position.
+        let start = Chess::default();
+        let mut bytes = polyglot::key(&start).to_be_bytes().to_vec();
+        bytes.extend((4u16 | 3 << 3 | 4 << 6 | 1 << 9).to_be_bytes());
+        bytes.extend([0, 1, 0, 0, 0, 0]);
+        let book = OpeningBook::from_bytes(&bytes, BookChoice::Best).unwrap();
+        let minimax = Minimax::default().with_book(book);
+
+        let best_move = minimax.best_move(&start, Budget::Depth(1), Arc::default());
+        assert_eq!(best_move.m.unwrap().to_string(), "e2e4");
+        assert_eq!(best_move.depth, 0);
+
+        // Out of the book the computer searches.
+        let chess = Chess::from_fen("4k3/8/4p3/3n4/4P3/8/8/3QK3 w - - 0 1").unwrap();
+        let best_move = minimax.best_move(&chess, Budget::Depth(1), Arc::default());
+        assert_eq!(best_move.m.unwrap().to_string(), "e4d5");
+        assert!(best_move.depth > 0);
+    }
+
+    /// Positions with a tactic for the player to move, the move that starts it, and how many
+    /// moves deep the search has to look to find it. Without the quiescence search, the
+    /// exchanges at the end of each line would have to be searched move by move as well.
+    const TACTICS: [(&str, &str, u8); 4] = [
+        // Capturing the knight with the pawn wins a piece, with the queen it loses the queen.
+        ("4k3/8/4p3/3n4/4P3/8/8/3QK3 w - - 0 1", "e4d5", 1),
+        // The knight forks the king and the queen.
+        ("q3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "b5c7", 2),
+        // The rook skewers the king and the queen, blocking the check costs the queen as well.
+        ("8/8/8/8/3k3q/8/8/RK6 w - - 0 1", "a1a4", 2),
+        // The same fork for black.
+        ("4k3/8/8/8/1n6/8/8/Q3K3 b - - 0 1", "b4c2", 2),
+    ];
+
+    #[test]
+    fn test_tactics() {
+        for (fen, uci, depth) in TACTICS {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            let best_move = minimax::<Positional>(
+                &mut chess,
+                &mut TranspositionTable::default(),
+                &mut MoveOrder::default(),
+                &mut Limit::unlimited(),
+                depth,
+                -Score::INFINITY,
+                Score::INFINITY,
+            );
+            assert_eq!(best_move.m.unwrap().to_string(), uci, "in {fen}");
+        }
+    }
+
+    /// Plays the moves the search finds for both players, until the game is over.
+    fn play_out(chess: &mut Chess) -> usize {
+        let mut table = TranspositionTable::default();
+        let mut moves = 0;
+        while chess.outcome().is_none() {
+            let best_move = search::<Positional>(chess, &mut table, Limit::unlimited());
+            chess.perform(best_move.m.unwrap());
+            moves += 1;
+        }
+        moves
+    }
+
+    #[test]
+    fn test_mate() {
+        // White mates with Rb8 or Ra8, but not in the same move.
+        let mate_in_1 = "7k/R7/8/8/8/8/1R6/7K w - - 0 1";
+        // The rooks push the king to the edge of the board one rank at a time.
+        let mate_in_2 = "7k/8/8/8/8/8/1R6/R6K w - - 0 1";
+        let mate_in_3 = "8/8/7k/1R6/8/8/8/R3K3 w - - 0 1";
+        for (fen, moves) in [(mate_in_1, 1), (mate_in_2, 2), (mate_in_3, 3)] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            let mut table = TranspositionTable::default();
+            let best_move = search::<Positional>(&mut chess, &mut table, Limit::unlimited());
+            assert_eq!(best_move.score.mate_in(), Some(moves), "in {fen}");
+
+            // Black defends as long as it can, and white mates as fast as it can.
+            assert_eq!(play_out(&mut chess), 2 * moves as usize - 1, "in {fen}");
+            assert_eq!(chess.outcome(), Some(Outcome::Winner(Color::White)));
+        }
+    }
+
+    #[test]
+    fn test_longest_defence() {
+        // Black is mated either way, but with the king on g8 one move sooner.
+        let mut chess = Chess::from_fen("7k/8/8/5K2/8/8/8/3Q4 b - - 0 1").unwrap();
+        let mut table = TranspositionTable::default();
+        let best_move = search::<Positional>(&mut chess, &mut table, Limit::unlimited());
+        assert_eq!(best_move.score.mate_in(), Some(3));
+        assert_ne!(best_move.m.unwrap().to_string(), "h8g8");
+    }
+
+    #[test]
+    fn test_shorter_mate() {
+        // The quiescence search finds a mate in 3 with checks one move deep, but there is a
+        // quiet mate in 2.
+        let mut chess = Chess::from_fen("1K5k/3P4/Q7/8/4P3/2R2b2/8/8 w - - 0 1").unwrap();
+        let best_move = minimax::<Positional>(
+            &mut chess,
+            &mut TranspositionTable::default(),
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            1,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        assert_eq!(best_move.score.mate_in(), Some(3));
+        let best_move = search::<Positional>(
+            &mut chess,
+            &mut TranspositionTable::default(),
+            Limit::unlimited(),
+        );
+        assert_eq!(best_move.score.mate_in(), Some(2));
+    }
+
+    #[test]
+    fn test_quiescence() {
+        // The pawn on d6 looks free, but it is defended by the pawn on e7.
+        let mut chess = Chess::from_fen("4k3/4p3/3p4/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let best_move = minimax::<Material>(
+            &mut chess,
+            &mut TranspositionTable::default(),
+            &mut MoveOrder::default(),
+            &mut Limit::unlimited(),
+            1,
+            -Score::INFINITY,
+            Score::INFINITY,
+        );
+        assert_ne!(best_move.m.unwrap().to_string(), "d1d6");
+        assert_eq!(best_move.score, Score::evaluation(7));
+        // Right after capturing, the position looks better for white than it is.
+        chess.perform(chess.parse_uci("d1d6").unwrap());
+        assert_eq!(Material::evaluate(&chess), 8);
+        let score = quiescence::<Material>(
+            &mut chess,
+            &MoveOrder::default(),
+            &mut Limit::unlimited(),
+            0,
+            -Score::INFINITY,
+            Score::INFINITY,
         );
+        assert_eq!(score, Score::evaluation(-1));
+    }
+
+    #[test]
+    fn test_difficulty() {
+        assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+        assert!("impossible".parse::<Difficulty>().is_err());
+        assert_eq!(Difficulty::Easy.to_string(), "easy");
     }
 }
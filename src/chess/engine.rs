@@ -0,0 +1,350 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::chess::{
+    chess::{Chess, Color},
+    computer::{BestMove, Budget, ComputerPlayer, Minimax},
+    score::Score,
+    variant::Rules,
+};
+
+/// How long to wait for a line of the engine before checking whether the search has to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the engine may take to start and get ready to search.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A chess engine that runs as a separate program, like Stockfish, and is controlled over the
+/// Universal Chess Interface (UCI). The engine gets the position and the [`Budget`] of every
+/// move, and answers with its best move.
+///
+/// When the engine fails, for example because it crashed, the built-in [`Minimax`] computer
+/// plays the move instead, so the game on the board can go on.
+pub struct UciEngine {
+    process: Mutex<Process>,
+    fallback: Minimax,
+}
+
+/// The running engine program.
+struct Process {
+    child: Child,
+    input: ChildStdin,
+    /// The lines the engine writes. They are read on a separate thread, so waiting for them can
+    /// be interrupted to stop the search.
+    output: Receiver<String>,
+    /// Whether the engine has been told to play Chess960.
+    chess960: bool,
+}
+
+/// The reasons why an engine can not play a move.
+#[derive(Debug)]
+pub enum EngineError {
+    /// Starting the program or writing to it failed.
+    Io(io::Error),
+    /// The engine closed its output, for example because it crashed.
+    Exited,
+    /// The engine did not give the expected answer in time, for example because the program is
+    /// not a UCI engine.
+    Timeout(String),
+    /// The engine answered with a move that can not be played in the position.
+    IllegalMove(String),
+    /// The game is a variant that UCI engines do not know the rules of.
+    Variant(Rules),
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Io(error) => write!(f, "{error}"),
+            EngineError::Exited => write!(f, "the engine has exited"),
+            EngineError::Timeout(answer) => write!(f, "the engine did not answer {answer} in time"),
+            EngineError::IllegalMove(m) => write!(f, "the engine played the illegal move {m}"),
+            EngineError::Variant(rules) => write!(f, "the engine does not play {rules}"),
+        }
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> Self {
+        EngineError::Io(error)
+    }
+}
+
+impl UciEngine {
+    /// Starts the engine program at the given path, and waits until it is ready to search.
+    pub fn start(path: &str) -> Result<Self, EngineError> {
+        UciEngine::start_within(path, STARTUP_TIMEOUT)
+    }
+
+    /// Starts the engine program at the given path, and waits at most the given time until it is
+    /// ready to search. An engine that is not ready in time is stopped.
+    fn start_within(path: &str, timeout: Duration) -> Result<Self, EngineError> {
+        let deadline = Instant::now() + timeout;
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut process = Process {
+            child,
+            input,
+            output,
+            chess960: false,
+        };
+        process.send("uci")?;
+        process.wait_for("uciok", deadline)?;
+        process.send("ucinewgame")?;
+        process.send("isready")?;
+        process.wait_for("readyok", deadline)?;
+        Ok(UciEngine {
+            process: Mutex::new(process),
+            fallback: Minimax::default(),
+        })
+    }
+
+    /// Lets the engine search the position within the budget, and reads its best move. The
+    /// score and depth are those of the last `info` the engine sent.
+    fn search(
+        &self,
+        chess: &Chess,
+        budget: Budget,
+        stop: &AtomicBool,
+    ) -> Result<BestMove, EngineError> {
+        if chess.rules != Rules::Standard {
+            return Err(EngineError::Variant(chess.rules));
+        }
+        let mut process = self.process.lock().unwrap();
+        if process.chess960 != chess.chess960 {
+            process.send(&format!(
+                "setoption name UCI_Chess960 value {}",
+                chess.chess960
+            ))?;
+            process.chess960 = chess.chess960;
+        }
+        process.send(&position_command(chess))?;
+        process.send(&go_command(budget))?;
+        let mut best_move = BestMove {
+            m: None,
+            score: Score::DRAW,
+            depth: 0,
+        };
+        let mut stopped = false;
+        loop {
+            let line = match process.output.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    // The engine still answers with a move after it is stopped, which has to be
+                    // read before the next search.
+                    if !stopped && stop.load(Ordering::Relaxed) {
+                        process.send("stop")?;
+                        stopped = true;
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(EngineError::Exited),
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["info", info @ ..] => read_info(info, chess.turn, &mut best_move),
+                ["bestmove", "0000" | "(none)", ..] if chess.outcome().is_some() => {
+                    return Ok(best_move);
+                }
+                ["bestmove", uci, ..] => {
+                    let m = chess
+                        .parse_uci(uci)
+                        .map_err(|_| EngineError::IllegalMove(uci.to_string()))?;
+                    best_move.m = Some(m);
+                    return Ok(best_move);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl ComputerPlayer for UciEngine {
+    fn best_move(&self, chess: &Chess, budget: Budget, stop: Arc<AtomicBool>) -> BestMove {
+        match self.search(chess, budget, &stop) {
+            Ok(best_move) => best_move,
+            Err(error) => {
+                println!("The engine can not play, the computer plays instead: {error}");
+                self.fallback.best_move(chess, budget, stop)
+            }
+        }
+    }
+}
+
+impl Process {
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.input, "{command}")?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    /// Skips the lines of the engine up to the given answer, which has to come before the
+    /// deadline. The engine is killed when it does not, so it can not hang when it is dropped.
+    fn wait_for(&mut self, answer: &str, deadline: Instant) -> Result<(), EngineError> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match self.output.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = self.child.kill();
+                    return Err(EngineError::Timeout(answer.to_owned()));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(EngineError::Exited),
+            };
+            if line.trim() == answer {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        if self.send("quit").is_err() || self.child.wait().is_err() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+/// The `position` command for the given position. It sends the position the game started from
+/// and the moves played since, so the engine knows about repetitions.
+fn position_command(chess: &Chess) -> String {
+    let mut start = chess.clone();
+    let mut moves = Vec::new();
+    while let Some(m) = start.undo() {
+        moves.push(m.to_string());
+    }
+    let mut command = format!("position fen {}", start.to_fen());
+    if !moves.is_empty() {
+        moves.reverse();
+        command += &format!(" moves {}", moves.join(" "));
+    }
+    command
+}
+
+fn go_command(budget: Budget) -> String {
+    match budget {
+        Budget::Time(time) => format!("go movetime {}", time.as_millis()),
+        Budget::Nodes(nodes) => format!("go nodes {nodes}"),
+        Budget::Depth(depth) => format!("go depth {depth}"),
+    }
+}
+
+/// Reads the depth and score of an `info` line into the best move. The engine gives the score
+/// from the side of the player to move, the [`Score`] is from the side of white.
+fn read_info(info: &[&str], turn: Color, best_move: &mut BestMove) {
+    if info.first() == Some(&"string") {
+        return;
+    }
+    let number = |name: &str| {
+        let at = info.iter().position(|word| *word == name)?;
+        info.get(at + 1)?.parse::<i16>().ok()
+    };
+    if let Some(depth) = number("depth") {
+        best_move.depth = depth.clamp(0, u8::MAX as i16) as u8;
+    }
+    best_move.score = match (number("cp"), number("mate")) {
+        (Some(centipawns), _) if turn == Color::White => Score::evaluation(centipawns),
+        (Some(centipawns), _) => -Score::evaluation(centipawns),
+        // A positive number of moves is a mate by the player to move, a negative one a mate
+        // against it.
+        (None, Some(moves)) if moves > 0 => Score::checkmated_after(!turn, 2 * moves.min(500) - 1),
+        (None, Some(moves)) => Score::checkmated_after(turn, -2 * moves.max(-500)),
+        (None, None) => return,
+    };
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::chess::{chess::Color, engine::*};
+
+    #[test]
+    fn test_commands() {
+        let mut chess = Chess::default();
+        assert_eq!(
+            position_command(&chess),
+            "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        for uci in ["e2e4", "e7e5"] {
+            let m = chess.parse_uci(uci).unwrap();
+            chess.perform(m);
+        }
+        assert!(position_command(&chess).ends_with("KQkq - 0 1 moves e2e4 e7e5"));
+
+        let time = Budget::Time(Duration::from_millis(500));
+        assert_eq!(go_command(time), "go movetime 500");
+        assert_eq!(go_command(Budget::Nodes(2_000)), "go nodes 2000");
+    }
+
+    #[test]
+    fn test_read_info() {
+        let mut best_move = BestMove {
+            m: None,
+            score: Score::DRAW,
+            depth: 0,
+        };
+        let info = "depth 12 seldepth 18 score cp 35 nodes 1000 pv e2e4 e7e5";
+        let info: Vec<&str> = info.split_whitespace().collect();
+        read_info(&info, Color::Black, &mut best_move);
+        assert_eq!(best_move.depth, 12);
+        assert_eq!(best_move.score, Score::evaluation(-35));
+
+        read_info(
+            &["depth", "3", "score", "mate", "2"],
+            Color::White,
+            &mut best_move,
+        );
+        assert_eq!(best_move.score.mate_in(), Some(2));
+        read_info(
+            &["depth", "3", "score", "mate", "-1"],
+            Color::White,
+            &mut best_move,
+        );
+        assert_eq!(best_move.score.mate_in(), Some(-1));
+        read_info(&["string", "depth", "5"], Color::White, &mut best_move);
+        assert_eq!(best_move.depth, 3);
+    }
+
+    #[test]
+    fn test_missing_engine() {
+        let error = UciEngine::start("./there-is-no-engine-here").err().unwrap();
+        assert!(matches!(error, EngineError::Io(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unresponsive_engine() {
+        // Cat repeats the commands, but never answers them.
+        let error = UciEngine::start_within("cat", Duration::from_millis(200))
+            .err()
+            .unwrap();
+        assert!(matches!(error, EngineError::Timeout(answer) if answer == "uciok"));
+    }
+}
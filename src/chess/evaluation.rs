@@ -0,0 +1,382 @@
+use crate::chess::{
+    bitboard::{self, Bitboard, KING_ATTACKS, KNIGHT_ATTACKS},
+    chess::{Chess, Color, Kind, Piece},
+    pos::Pos,
+};
+
+/// Scores positions for the search of the chess computer. A positive score means that white is
+/// better off, and a negative score that black is. The search only compares scores, so the unit
+/// of the score is up to the evaluator.
+///
+/// Positions in which the game is over are scored by the search itself, so an evaluator is only
+/// used for positions in which the player to move has a legal move.
+pub trait Evaluator {
+    fn evaluate(chess: &Chess) -> i16;
+}
+
+/// Scores a position by the base values of the pieces on the board only.
+pub struct Material;
+
+impl Evaluator for Material {
+    fn evaluate(chess: &Chess) -> i16 {
+        chess.material()
+    }
+}
+
+/// The evaluator of the chess computer, which scores a position in centipawns, hundredths of a
+/// pawn. Besides the material on the board it looks at:
+///
+/// * where the pieces stand, with a piece-square table for each kind of piece,
+/// * how many squares the knights, bishops, rooks and queens can move to,
+/// * the pawn structure: doubled, isolated and passed pawns,
+/// * the pawns that shield the king.
+///
+/// A piece or square is not worth the same in the middlegame and in the endgame: the king has to
+/// hide while the queens are on the board, but should come to the centre once most pieces are
+/// gone. So each term has a value for both, and the score is blended from them by how much of
+/// the pieces besides the pawns is left.
+pub struct Positional;
+
+impl Evaluator for Positional {
+    fn evaluate(chess: &Chess) -> i16 {
+        let mut middlegame = 0;
+        let mut endgame = 0;
+        let mut phase = 0;
+        for color in [Color::White, Color::Black] {
+            let terms = Terms::of(chess, color);
+            let sign = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+            middlegame += sign * terms.middlegame;
+            endgame += sign * terms.endgame;
+            phase += terms.phase;
+        }
+        let phase = phase.min(MAX_PHASE);
+        ((middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE) as i16
+    }
+}
+
+/// The terms of the [`Positional`] evaluation for the pieces of one color.
+#[derive(Default)]
+struct Terms {
+    middlegame: i32,
+    endgame: i32,
+    /// How much is left of the pieces, from 0 with only kings and pawns up to [`MAX_PHASE`] for
+    /// all pieces of the start position.
+    phase: i32,
+}
+
+impl Terms {
+    fn of(chess: &Chess, color: Color) -> Self {
+        let mut terms = Terms::default();
+        let own = chess.color_squares(color);
+        let occupied = own | chess.color_squares(!color);
+        for kind in KINDS {
+            let index = kind.index();
+            for pos in bitboard::positions(chess.piece_squares(Piece { color, kind })) {
+                let square = table_square(color, pos);
+                terms.add(
+                    MIDDLEGAME_VALUES[index] + MIDDLEGAME_TABLES[index][square],
+                    ENDGAME_VALUES[index] + ENDGAME_TABLES[index][square],
+                );
+                terms.phase += PHASE[index];
+
+                let moves = match kind {
+                    Kind::Knight => KNIGHT_ATTACKS[bitboard::square(pos)],
+                    Kind::Bishop => bitboard::bishop_attacks(pos, occupied),
+                    Kind::Rook => bitboard::rook_attacks(pos, occupied),
+                    Kind::Queen => {
+                        bitboard::bishop_attacks(pos, occupied)
+                            | bitboard::rook_attacks(pos, occupied)
+                    }
+                    Kind::Pawn | Kind::King => 0,
+                };
+                let mobility = (moves & !own).count_ones() as i32;
+                terms.add(
+                    mobility * MIDDLEGAME_MOBILITY[index],
+                    mobility * ENDGAME_MOBILITY[index],
+                );
+            }
+        }
+        terms.add_pawn_structure(chess, color);
+        terms.add_king_shield(chess, color);
+        terms
+    }
+
+    fn add(&mut self, middlegame: i32, endgame: i32) {
+        self.middlegame += middlegame;
+        self.endgame += endgame;
+    }
+
+    fn add_pawn_structure(&mut self, chess: &Chess, color: Color) {
+        let pawns = chess.piece_squares(Piece {
+            color,
+            kind: Kind::Pawn,
+        });
+        let enemy_pawns = chess.piece_squares(Piece {
+            color: !color,
+            kind: Kind::Pawn,
+        });
+        for x in 0..8 {
+            let doubled = (pawns & file(x)).count_ones().saturating_sub(1) as i32;
+            self.add(doubled * DOUBLED.0, doubled * DOUBLED.1);
+        }
+        for pos in bitboard::positions(pawns) {
+            let neighbours = adjacent_files(pos.x());
+            if pawns & neighbours == 0 {
+                self.add(ISOLATED.0, ISOLATED.1);
+            }
+            // No enemy pawn in front of the pawn can stop it or capture it on its way.
+            if enemy_pawns & (neighbours | file(pos.x())) & ahead(color, pos) == 0 {
+                let rank = table_rank(color, pos);
+                self.add(MIDDLEGAME_PASSED[rank], ENDGAME_PASSED[rank]);
+            }
+        }
+    }
+
+    /// Only a royal king needs a shield, in other variants the king may not even be on the board.
+    fn add_king_shield(&mut self, chess: &Chess, color: Color) {
+        if !chess.rules.variant().royal_king() {
+            return;
+        }
+        let king = chess.kings[color.king_index()];
+        let pawns = chess.piece_squares(Piece {
+            color,
+            kind: Kind::Pawn,
+        });
+        let shield = KING_ATTACKS[bitboard::square(king)] & pawns & ahead(color, king);
+        self.add(shield.count_ones() as i32 * SHIELD, 0);
+    }
+}
+
+/// All kinds of pieces, in the order of [`Kind::index`].
+const KINDS: [Kind; 6] = [
+    Kind::Pawn,
+    Kind::Rook,
+    Kind::Knight,
+    Kind::Bishop,
+    Kind::Queen,
+    Kind::King,
+];
+
+/// The [`Terms::phase`] of the start position.
+const MAX_PHASE: i32 = 24;
+/// How much each kind of piece adds to the [`Terms::phase`].
+const PHASE: [i32; 6] = [0, 2, 1, 1, 4, 0];
+
+/// The values of the pieces in centipawns, in the order of [`Kind::index`].
+const MIDDLEGAME_VALUES: [i32; 6] = [82, 477, 337, 365, 1025, 0];
+const ENDGAME_VALUES: [i32; 6] = [94, 512, 281, 297, 936, 0];
+
+/// What each square a piece can move to is worth, in the order of [`Kind::index`].
+const MIDDLEGAME_MOBILITY: [i32; 6] = [0, 2, 4, 5, 1, 0];
+const ENDGAME_MOBILITY: [i32; 6] = [0, 4, 4, 5, 2, 0];
+
+/// The penalties for each extra pawn on a file, and for each pawn without pawns of its own color
+/// on the files next to it, in the middlegame and in the endgame.
+const DOUBLED: (i32, i32) = (-10, -20);
+const ISOLATED: (i32, i32) = (-10, -15);
+/// The bonus for a passed pawn on each rank, counted from the own side of the board.
+const MIDDLEGAME_PASSED: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const ENDGAME_PASSED: [i32; 8] = [0, 10, 20, 35, 60, 90, 130, 0];
+/// The bonus for each pawn right in front of the king in the middlegame.
+const SHIELD: i32 = 10;
+
+/// The squares of the a-file.
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+fn file(x: isize) -> Bitboard {
+    FILE_A << x
+}
+
+fn adjacent_files(x: isize) -> Bitboard {
+    let left = if x > 0 { file(x - 1) } else { 0 };
+    let right = if x < 7 { file(x + 1) } else { 0 };
+    left | right
+}
+
+/// Returns the squares on the ranks in front of the given position, seen from the given color.
+fn ahead(color: Color, pos: Pos) -> Bitboard {
+    match color {
+        Color::White if pos.y() < 7 => !0 << (8 * (pos.y() + 1)),
+        Color::Black => (1 << (8 * pos.y())) - 1,
+        Color::White => 0,
+    }
+}
+
+/// Returns the rank of a position counted from the side of the given color, 0 for its back rank.
+fn table_rank(color: Color, pos: Pos) -> usize {
+    match color {
+        Color::White => pos.y() as usize,
+        Color::Black => 7 - pos.y() as usize,
+    }
+}
+
+/// Returns the index of a position in the piece-square tables, which are written down as white
+/// sees the board: a8 first and h1 last. The tables are mirrored for black.
+fn table_square(color: Color, pos: Pos) -> usize {
+    (7 - table_rank(color, pos)) * 8 + pos.x() as usize
+}
+
+/// The piece-square tables, in the order of [`Kind::index`].
+const MIDDLEGAME_TABLES: [[i32; 64]; 6] = [
+    PAWN_MIDDLEGAME,
+    ROOK,
+    KNIGHT,
+    BISHOP,
+    QUEEN,
+    KING_MIDDLEGAME,
+];
+const ENDGAME_TABLES: [[i32; 64]; 6] = [PAWN_ENDGAME, ROOK, KNIGHT, BISHOP, QUEEN, KING_ENDGAME];
+
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{chess::Chess, evaluation::*, fen::START_FEN};
+
+    fn positional(fen: &str) -> i16 {
+        Positional::evaluate(&Chess::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn test_material() {
+        assert_eq!(Material::evaluate(&Chess::default()), 0);
+        // Black is missing its queen.
+        let chess = Chess::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(Material::evaluate(&chess.unwrap()), 9);
+    }
+
+    #[test]
+    fn test_positional() {
+        // Both players stand the same at the start.
+        assert_eq!(positional(START_FEN), 0);
+        // A knight in the centre is better than a knight on the rim.
+        let centre = positional("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1");
+        let rim = positional("rnbqkbnr/pppppppp/8/8/8/7N/PPPPPPPP/RNBQKB1R b KQkq - 1 1");
+        assert!(centre > rim);
+        assert!(centre > 0);
+        // The same position with the colors swapped gets the opposite score.
+        let black = positional("rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 1 1");
+        assert_eq!(black, -centre);
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        // The pawn on d5 is passed when no black pawn in front of it can stop it.
+        let passed = positional("4k3/p7/8/3P4/8/8/8/4K3 w - - 0 1");
+        let stopped = positional("4k3/4p3/8/3P4/8/8/8/4K3 w - - 0 1");
+        assert!(passed > stopped);
+        // Two pawns next to each other are better than two pawns on the same file.
+        let connected = positional("4k3/8/8/8/8/4P3/3P4/4K3 w - - 0 1");
+        let doubled = positional("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1");
+        assert!(connected > doubled);
+    }
+
+    #[test]
+    fn test_tapered() {
+        // In the endgame the king belongs in the centre.
+        let centre = positional("4k3/8/8/8/4K3/8/8/8 w - - 0 1");
+        let corner = positional("4k3/8/8/8/8/8/8/6K1 w - - 0 1");
+        assert!(centre > corner);
+        // With all pieces on the board it is better off behind its pawns.
+        let centre = positional("rnbqkbnr/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1BNR w kq - 0 1");
+        assert!(positional(START_FEN) > centre);
+    }
+}
@@ -0,0 +1,462 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chess::{
+    chess::{Chess, Color, Kind, Piece},
+    pos::Pos,
+    variant::Rules,
+};
+
+/// FEN of the position at the start of a game.
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The reasons why a string can not be read as a FEN.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FenError {
+    /// There are not 4 to 6 fields separated by whitespace.
+    Fields,
+    /// The piece placement is not made up of 8 rows of 8 squares each, or contains an unknown piece.
+    Board,
+    /// A player does not have exactly one king, in a variant with a royal king.
+    Kings,
+    /// The active color is not `w` or `b`.
+    Turn,
+    /// The castling field is not `-` or a combination of `KQkq` or the files of the rooks, whose
+    /// king and rook are still on their starting squares.
+    Castling,
+    /// The en passant field is not `-` or the square right behind a pawn that just leaped.
+    EnPassant,
+    /// The halfmove clock is not a number, or the fullmove number is not a positive number.
+    Clock,
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::Fields => write!(f, "a FEN needs 4 to 6 fields"),
+            FenError::Board => write!(f, "invalid piece placement"),
+            FenError::Kings => write!(f, "each player needs exactly one king"),
+            FenError::Turn => write!(f, "invalid active color"),
+            FenError::Castling => write!(f, "invalid castling rights"),
+            FenError::EnPassant => write!(f, "invalid en passant square"),
+            FenError::Clock => write!(f, "invalid move clock"),
+        }
+    }
+}
+
+impl Chess {
+    /// Reads a position from Forsyth-Edwards Notation. Like in EPD, the two move clocks at the
+    /// end may be left out, in which case those of a new game are used. The graveyards of the
+    /// position are empty, and no earlier positions are known to be repeated.
+    ///
+    /// Chess960 positions are read from X-FEN and Shredder-FEN, which write a castling right as
+    /// the file of the rook, like `HAha`, instead of `KQkq` when that rook is not the outermost
+    /// one. A position is read as Chess960 when a file is used, or when a player may castle with
+    /// a king or rook that is not on the squares of classical chess.
+    pub fn from_fen(fen: &str) -> Result<Chess, FenError> {
+        Chess::from_fen_with_rules(fen, Rules::Standard)
+    }
+
+    /// Reads a position from Forsyth-Edwards Notation, like [`from_fen`], for a game played by
+    /// the given rules. In a variant without a royal king, like Antichess, a player may have any
+    /// number of kings, and the castling field is ignored as there is no castling.
+    ///
+    /// [`from_fen`]: #method.from_fen
+    pub fn from_fen_with_rules(fen: &str, rules: Rules) -> Result<Chess, FenError> {
+        let royal_king = rules.variant().royal_king();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&fields.len()) {
+            return Err(FenError::Fields);
+        }
+        let mut chess = Chess::empty();
+
+        // The rows are listed from black's side of the board (row 7) down to white's (row 0).
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != 8 {
+            return Err(FenError::Board);
+        }
+        for (y, row) in (0..8).rev().zip(rows) {
+            let mut x = 0;
+            for c in row.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|empty| (1..=8).contains(empty)) {
+                    x += empty as isize;
+                } else {
+                    let piece = piece_from_char(c).ok_or(FenError::Board)?;
+                    if x >= 8 {
+                        return Err(FenError::Board);
+                    }
+                    chess.set(Pos::new(x, y), Some(piece));
+                    x += 1;
+                }
+            }
+            if x != 8 {
+                return Err(FenError::Board);
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            let king = Piece {
+                color,
+                kind: Kind::King,
+            };
+            let mut kings = Chess::board_positions().filter(|pos| chess[pos] == Some(king));
+            match (kings.next(), kings.next()) {
+                (Some(pos), None) => chess.kings[color.king_index()] = pos,
+                (Some(pos), Some(_)) if !royal_king => chess.kings[color.king_index()] = pos,
+                (None, _) if !royal_king => {}
+                _ => return Err(FenError::Kings),
+            }
+        }
+
+        chess.turn = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::Turn),
+        };
+
+        if fields[2] != "-" && royal_king {
+            let mut columns: [[Option<isize>; 2]; 2] = [[None; 2]; 2];
+            for c in fields[2].chars() {
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                let row = color.back_rank();
+                let king = chess.kings[color.king_index()];
+                let rook = Some(Piece {
+                    color,
+                    kind: Kind::Rook,
+                });
+                let is_rook = |x: &isize| chess[Pos::new(*x, row)] == rook;
+                // `K` and `Q` stand for the outermost rook on that side of the king.
+                let rook_x = match c.to_ascii_uppercase() {
+                    'K' => (king.x() + 1..8).rev().find(is_rook),
+                    'Q' => (0..king.x()).find(is_rook),
+                    file @ 'A'..='H' => Some((file as u8 - b'A') as isize).filter(is_rook),
+                    _ => None,
+                };
+                let rook_x = rook_x.ok_or(FenError::Castling)?;
+                if king.y() != row {
+                    return Err(FenError::Castling);
+                }
+                let rights = &mut chess.castling_rights[color.king_index()];
+                let side = if rook_x > king.x() {
+                    rights.king_side = true;
+                    0
+                } else {
+                    rights.queen_side = true;
+                    1
+                };
+                // A player castles to each side with a single rook.
+                if *columns[color.king_index()][side].get_or_insert(rook_x) != rook_x {
+                    return Err(FenError::Castling);
+                }
+                let file = !matches!(c, 'K' | 'Q' | 'k' | 'q');
+                if file || king.x() != 4 || rook_x != [7, 0][side] {
+                    chess.chess960 = true;
+                }
+            }
+            for (player, player_columns) in columns.into_iter().enumerate() {
+                for (side, column) in player_columns.into_iter().enumerate() {
+                    if let Some(column) = column {
+                        chess.castling_columns[player][side] = column;
+                    }
+                }
+            }
+        }
+
+        chess.en_passant = match fields[3] {
+            "-" => None,
+            square => {
+                let pos: Pos = square.parse().map_err(|_| FenError::EnPassant)?;
+                // The pawn of the player that just moved leaped over this square.
+                let (row, pawn) = match chess.turn {
+                    Color::White => (5, Pos::new(pos.x(), 4)),
+                    Color::Black => (2, Pos::new(pos.x(), 3)),
+                };
+                let leaped = Some(Piece {
+                    color: !chess.turn,
+                    kind: Kind::Pawn,
+                });
+                if pos.y() != row || chess[pawn] != leaped || chess[pos].is_some() {
+                    return Err(FenError::EnPassant);
+                }
+                Some(pos)
+            }
+        };
+
+        if let Some(clock) = fields.get(4) {
+            chess.halfmove_clock = clock.parse().map_err(|_| FenError::Clock)?;
+        }
+        if let Some(number) = fields.get(5) {
+            chess.fullmove_number = number.parse().map_err(|_| FenError::Clock)?;
+            if chess.fullmove_number == 0 {
+                return Err(FenError::Clock);
+            }
+        }
+
+        chess.set_rules(rules);
+        Ok(chess)
+    }
+
+    /// Writes the position in Forsyth-Edwards Notation. The castling rights of a Chess960
+    /// position are written like in X-FEN: as `KQkq` when the rook is the outermost one on its
+    /// side, and as the file of the rook otherwise.
+    pub fn to_fen(&self) -> String {
+        let rows: Vec<String> = (0..8)
+            .rev()
+            .map(|y| {
+                let mut row = String::new();
+                let mut empty = 0;
+                for x in 0..8 {
+                    match self[Pos::new(x, y)] {
+                        None => empty += 1,
+                        Some(piece) => {
+                            if empty > 0 {
+                                row.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            row.push(piece_to_char(piece));
+                        }
+                    }
+                }
+                if empty > 0 {
+                    row.push_str(&empty.to_string());
+                }
+                row
+            })
+            .collect();
+
+        let turn = match self.turn {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let mut castling = String::new();
+        for color in [Color::White, Color::Black] {
+            let rights = self.castling_rights[color.king_index()];
+            let row = color.back_rank();
+            let rook = Some(Piece {
+                color,
+                kind: Kind::Rook,
+            });
+            let [king_side, queen_side] = self.castling_columns[color.king_index()];
+            // (right, rook column, letter, columns outside the rook)
+            let sides = [
+                (rights.king_side, king_side, 'K', king_side + 1..8),
+                (rights.queen_side, queen_side, 'Q', 0..queen_side),
+            ];
+            for (right, rook_x, letter, mut outside) in sides {
+                if !right {
+                    continue;
+                }
+                let letter = if outside.any(|x| self[Pos::new(x, row)] == rook) {
+                    (b'A' + rook_x as u8) as char
+                } else {
+                    letter
+                };
+                castling.push(match color {
+                    Color::White => letter,
+                    Color::Black => letter.to_ascii_lowercase(),
+                });
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = self
+            .en_passant
+            .map(|pos| pos.to_string())
+            .unwrap_or_else(|| "-".to_owned());
+
+        format!(
+            "{} {} {} {} {} {}",
+            rows.join("/"),
+            turn,
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+}
+
+/// Returns the piece for a letter in the piece placement, uppercase for white and lowercase for
+/// black.
+fn piece_from_char(c: char) -> Option<Piece> {
+    let kind = Kind::from_letter(c.to_ascii_uppercase())?;
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    Some(Piece { color, kind })
+}
+
+/// Returns the letter of a piece in the piece placement.
+fn piece_to_char(piece: Piece) -> char {
+    match piece.color {
+        Color::White => piece.kind.letter(),
+        Color::Black => piece.kind.letter().to_ascii_lowercase(),
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Move, Piece},
+        fen::*,
+        pos::Pos,
+        variant::Rules,
+    };
+
+    #[test]
+    fn test_start_fen() {
+        assert_eq!(Chess::from_fen(START_FEN), Ok(Chess::default()));
+        assert_eq!(Chess::default().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let mut chess = Chess::default();
+        chess.perform(Move::new(Pos::new(4, 1), Pos::new(4, 3)));
+        let fen = chess.to_fen();
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        // The moves that led to the position are not part of the FEN.
+        let read = Chess::from_fen(&fen).unwrap();
+        assert_eq!(read.history, vec![]);
+        chess.history.clear();
+        assert_eq!(read, chess);
+
+        let mut chess = read;
+        for m in [
+            Move::new(Pos::new(6, 7), Pos::new(5, 5)),
+            Move::new(Pos::new(6, 0), Pos::new(5, 2)),
+        ] {
+            chess.perform(m);
+        }
+        assert_eq!(
+            chess.to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 2"
+        );
+    }
+
+    #[test]
+    fn test_from_fen() {
+        // Both kings castled, with the clocks left out like in EPD.
+        let chess = Chess::from_fen("r4rk1/8/8/8/8/8/8/2KR3R w - -").unwrap();
+        assert_eq!(chess.kings, [Pos::new(2, 0), Pos::new(6, 7)]);
+        assert_eq!(chess[Pos::new(3, 0)], Some(Piece::WHITE_ROOK));
+        assert_eq!(chess[Pos::new(0, 7)], Some(Piece::BLACK_ROOK));
+        assert!(!chess.castling_rights[0].king_side);
+        assert!(!chess.castling_rights[1].queen_side);
+        assert_eq!(chess.en_passant, None);
+        assert_eq!((chess.halfmove_clock, chess.fullmove_number), (0, 1));
+
+        let chess = Chess::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 37 81").unwrap();
+        assert_eq!((chess.halfmove_clock, chess.fullmove_number), (37, 81));
+    }
+
+    #[test]
+    fn test_chess960_fen() {
+        let chess = Chess::chess960(0);
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1";
+        assert_eq!(chess.to_fen(), fen);
+        assert_eq!(Chess::from_fen(fen), Ok(chess.clone()));
+        // Shredder-FEN writes the files of the rooks.
+        assert_eq!(Chess::from_fen(&fen.replace("KQkq", "HFhf")), Ok(chess));
+
+        // A rook that is not the outermost one is written as its file.
+        let fen = "4k3/8/8/8/8/8/8/1R2K1RR w GB - 0 1";
+        let chess = Chess::from_fen(fen).unwrap();
+        assert!(chess.chess960);
+        assert_eq!(chess.castling_columns, [[6, 1], [7, 0]]);
+        assert_eq!(chess.to_fen(), fen.replace("GB", "GQ"));
+
+        // Each player castles with its own rooks.
+        let fen = "1r2k2r/8/8/8/8/8/8/R3K2R b HAhb - 0 1";
+        let mut chess = Chess::from_fen(fen).unwrap();
+        assert!(chess.chess960);
+        assert_eq!(chess.castling_columns, [[7, 0], [7, 1]]);
+        assert_eq!(chess.to_fen(), fen.replace("HAhb", "KQkq"));
+        let castling = Move::new(Pos::new(4, 7), Pos::new(1, 7));
+        assert!(chess.moves().any(|m| m == castling));
+        chess.perform(castling);
+        assert_eq!(chess[Pos::new(2, 7)], Some(Piece::BLACK_KING));
+        assert_eq!(chess[Pos::new(3, 7)], Some(Piece::BLACK_ROOK));
+        assert!(chess.castling_rights[0].queen_side);
+
+        // Classical positions stay classical.
+        assert!(!Chess::from_fen(START_FEN).unwrap().chess960);
+        assert!(
+            Chess::from_fen("4k3/8/8/8/8/8/8/R2K3R w KQ - 0 1")
+                .unwrap()
+                .chess960
+        );
+    }
+
+    #[test]
+    fn test_fen_with_rules() {
+        // In Antichess a player may have lost its king, or have promoted pawns to kings.
+        let fen = "8/8/8/8/8/8/3k4/2K1K3 w - - 0 1";
+        assert_eq!(Chess::from_fen(fen), Err(FenError::Kings));
+        let chess = Chess::from_fen_with_rules(fen, Rules::Antichess).unwrap();
+        assert_eq!(chess.rules, Rules::Antichess);
+        assert_eq!(chess.to_fen(), fen);
+        let fen = "8/8/8/8/8/8/3p4/4K3 b - - 0 1";
+        assert!(Chess::from_fen_with_rules(fen, Rules::Antichess).is_ok());
+
+        // Without castling, the castling field is ignored.
+        let chess = Chess::from_fen_with_rules(START_FEN, Rules::Antichess).unwrap();
+        assert_eq!(
+            chess.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+        );
+        assert_eq!(
+            Chess::from_fen_with_rules("4k3/8/8/8/8/8/8/8 w - - 0 1", Rules::KingOfTheHill),
+            Err(FenError::Kings)
+        );
+    }
+
+    #[test]
+    fn test_invalid_fen() {
+        assert_eq!(Chess::from_fen(""), Err(FenError::Fields));
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            Err(FenError::Board)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"),
+            Err(FenError::Kings)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1"),
+            Err(FenError::Turn)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            Err(FenError::Castling)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkC - 0 1"),
+            Err(FenError::Castling)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1"),
+            Err(FenError::EnPassant)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - zero 1"),
+            Err(FenError::Clock)
+        );
+        assert_eq!(
+            Chess::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"),
+            Err(FenError::Clock)
+        );
+    }
+}
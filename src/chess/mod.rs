@@ -1,15 +1,56 @@
-use self::chess::Chess;
+use self::{chess::Chess, pgn::GameRecord};
 use bevy::prelude::*;
 
+/// Measuring the speed of move generation and search.
+pub mod bench;
+/// Sets of squares stored as the bits of a number, to find the moves of pieces quickly.
+pub mod bitboard;
 /// Handles the boardstate and the rules of chess.
+#[allow(clippy::module_inception)]
 pub mod chess;
+/// The clocks of the players, which count down the time they have left for the game.
+pub mod clock;
 /// Chess computer
 pub mod computer;
+/// Letting a chess engine that runs as a separate program play as the computer player.
+pub mod engine;
+/// Scoring positions for the search of the chess computer.
+pub mod evaluation;
+/// Reading and writing positions in Forsyth-Edwards Notation.
+pub mod fen;
+/// Reading and writing moves in Standard Algebraic Notation and UCI long notation.
+pub mod notation;
+/// Sorting the moves of a position, so the search tries the most promising moves first.
+pub mod ordering;
+/// Counting the positions reached by the generated moves, to test move generation.
+pub mod perft;
+/// Recording games and reading and writing them in Portable Game Notation.
+pub mod pgn;
+/// Reading opening books in the Polyglot format, to play the first moves of a game from a book.
+pub mod polyglot;
 /// Module that makes it easy to deal with positions on the board
 pub mod pos;
+/// Scores of positions in the search, which tell how far away a mate is.
+pub mod score;
+/// Matches between configurations of the chess computer, to measure which one is stronger.
+pub mod selfplay;
+/// Remembering the results of searched positions.
+pub mod transposition;
+/// Playing as an engine for other chess programs, over the Universal Chess Interface.
+pub mod uci;
+/// Variants of chess with other rules, like King of the Hill.
+pub mod variant;
+/// Keys of positions that are updated with every move.
+pub mod zobrist;
 
 ///Resource variant of [`Chess`]
 #[derive(Resource, Default)]
 pub struct BoardState {
     pub chess: Chess,
 }
+
+///Resource variant of [`GameRecord`], recording the game that is being played.
+#[derive(Resource)]
+pub struct GameHistory {
+    pub game: GameRecord,
+}
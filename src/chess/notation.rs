@@ -0,0 +1,239 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chess::{
+    chess::{Chess, Kind, Move},
+    pos::Pos,
+};
+
+/// The reasons why a string can not be read as a move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NotationError {
+    /// The string is not written in the expected notation.
+    Syntax,
+    /// The string describes a move that is not legal in the current position.
+    Illegal,
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::Syntax => write!(f, "not a move in the expected notation"),
+            NotationError::Illegal => write!(f, "not a legal move in this position"),
+        }
+    }
+}
+
+/// Writes the move in UCI long notation, for example `e2e4` or `e7e8q`.
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(kind) = self.promotion {
+            write!(f, "{}", kind.letter().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+impl Move {
+    /// Reads a move in UCI long notation, for example `e2e4` or `e7e8q`. Whether the move is legal
+    /// is not checked, use [`Chess::parse_uci`] for that.
+    pub fn from_uci(uci: &str) -> Result<Move, NotationError> {
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return Err(NotationError::Syntax);
+        }
+        let from: Pos = uci[0..2].parse().map_err(|_| NotationError::Syntax)?;
+        let to: Pos = uci[2..4].parse().map_err(|_| NotationError::Syntax)?;
+        let promotion = match uci[4..].chars().next() {
+            None => None,
+            Some(letter) => match Kind::from_letter(letter.to_ascii_uppercase()) {
+                Some(kind @ (Kind::Queen | Kind::Rook | Kind::Bishop | Kind::Knight)) => Some(kind),
+                _ => return Err(NotationError::Syntax),
+            },
+        };
+        Ok(Move {
+            from,
+            to,
+            promotion,
+        })
+    }
+}
+
+impl Chess {
+    /// Writes a legal move of the current player in Standard Algebraic Notation, for example
+    /// `Nf3`, `exd5`, `O-O` or `e8=Q+`. When more pieces of the same kind can move to the same
+    /// square, the file, the row, or both of the moving piece are added.
+    pub fn san(&self, m: Move) -> String {
+        let piece = self[m.from].unwrap();
+        let mut san = String::new();
+
+        if let Some(castling) = self.castling(m) {
+            san.push_str(if castling.rook.from.x() > m.from.x() {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            let capture = self.captured_pos(m).is_some();
+            if piece.kind == Kind::Pawn {
+                if capture {
+                    san.push((m.from.x() as u8 + b'a') as char);
+                }
+            } else {
+                san.push(piece.kind.letter());
+                let others: Vec<Pos> = self
+                    .moves()
+                    .filter(|other| {
+                        other.to == m.to
+                            && other.from != m.from
+                            && self[other.from].map(|other| other.kind) == Some(piece.kind)
+                    })
+                    .map(|other| other.from)
+                    .collect();
+                if !others.is_empty() {
+                    let file = (m.from.x() as u8 + b'a') as char;
+                    let row = (m.from.y() as u8 + b'1') as char;
+                    if others.iter().all(|other| other.x() != m.from.x()) {
+                        san.push(file);
+                    } else if others.iter().all(|other| other.y() != m.from.y()) {
+                        san.push(row);
+                    } else {
+                        san.push(file);
+                        san.push(row);
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&m.to.to_string());
+            if let Some(kind) = m.promotion {
+                san.push('=');
+                san.push(kind.letter());
+            }
+        }
+
+        let mut after = self.clone();
+        after.perform(m);
+        if after.rules.variant().royal_king() && after.is_checked(after.turn) {
+            san.push(if after.moves().next().is_none() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    /// Reads a move of the current player in Standard Algebraic Notation. Check and mate signs
+    /// and annotations like `!` or `?` may be left out, and castling may be written with zeroes.
+    pub fn parse_san(&self, san: &str) -> Result<Move, NotationError> {
+        let san = normalize_san(san);
+        if san.is_empty() {
+            return Err(NotationError::Syntax);
+        }
+        self.moves()
+            .find(|m| normalize_san(&self.san(*m)) == san)
+            .ok_or(NotationError::Illegal)
+    }
+
+    /// Reads a move of the current player in UCI long notation, and checks whether it is legal.
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, NotationError> {
+        let m = Move::from_uci(uci)?;
+        self.moves()
+            .find(|legal| *legal == m)
+            .ok_or(NotationError::Illegal)
+    }
+}
+
+/// Strips the check and mate signs and annotations from a move in SAN, so moves can be compared
+/// no matter how they were written.
+fn normalize_san(san: &str) -> String {
+    san.trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O")
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Kind, Move},
+        notation::*,
+        pos::Pos,
+    };
+
+    /// Plays a list of moves in SAN from the start position.
+    fn play(moves: &[&str]) -> Chess {
+        let mut chess = Chess::default();
+        for san in moves {
+            let m = chess.parse_san(san).unwrap();
+            chess.perform(m);
+        }
+        chess
+    }
+
+    #[test]
+    fn test_uci() {
+        let m = Move::from_uci("e2e4").unwrap();
+        assert_eq!(m, Move::new(Pos::new(4, 1), Pos::new(4, 3)));
+        assert_eq!(m.to_string(), "e2e4");
+
+        let promotion = Move::from_uci("e7e8q").unwrap();
+        assert_eq!(promotion.promotion, Some(Kind::Queen));
+        assert_eq!(promotion.to_string(), "e7e8q");
+
+        assert_eq!(Move::from_uci("e2e9"), Err(NotationError::Syntax));
+        assert_eq!(Move::from_uci("e7e8k"), Err(NotationError::Syntax));
+        assert_eq!(
+            Chess::default().parse_uci("e2e5"),
+            Err(NotationError::Illegal)
+        );
+    }
+
+    #[test]
+    fn test_san() {
+        let chess = Chess::default();
+        assert_eq!(chess.san(Move::new(Pos::new(6, 0), Pos::new(5, 2))), "Nf3");
+        assert_eq!(chess.san(Move::new(Pos::new(4, 1), Pos::new(4, 3))), "e4");
+
+        let chess = play(&["e4", "d5"]);
+        assert_eq!(chess.san(Move::new(Pos::new(4, 3), Pos::new(3, 4))), "exd5");
+
+        let chess = play(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6"]);
+        assert_eq!(chess.san(Move::new(Pos::new(4, 0), Pos::new(6, 0))), "O-O");
+
+        // The fool's mate ends with a mate sign.
+        let chess = play(&["f3", "e5", "g4"]);
+        assert_eq!(chess.san(Move::new(Pos::new(3, 7), Pos::new(7, 3))), "Qh4#");
+    }
+
+    #[test]
+    fn test_san_promotion_and_disambiguation() {
+        let chess = Chess::from_fen("4k3/P7/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        let promotion = Move {
+            promotion: Some(Kind::Queen),
+            ..Move::new(Pos::new(0, 6), Pos::new(0, 7))
+        };
+        assert_eq!(chess.san(promotion), "a8=Q+");
+        assert_eq!(chess.parse_san("a8=Q+"), Ok(promotion));
+        // Both rooks can reach d1.
+        assert_eq!(chess.san(Move::new(Pos::new(0, 0), Pos::new(3, 0))), "Rad1");
+        assert_eq!(chess.san(Move::new(Pos::new(7, 0), Pos::new(5, 0))), "Rhf1");
+        assert_eq!(chess.san(Move::new(Pos::new(7, 0), Pos::new(7, 5))), "Rh6");
+
+        // Two knights on the same file are told apart by their row.
+        let chess = Chess::from_fen("4k3/8/8/1N6/8/1N6/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(chess.san(Move::new(Pos::new(1, 4), Pos::new(3, 3))), "N5d4");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let chess = play(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6"]);
+        assert_eq!(
+            chess.parse_san("0-0"),
+            Ok(Move::new(Pos::new(4, 0), Pos::new(6, 0)))
+        );
+        assert_eq!(chess.parse_san("Ke3"), Err(NotationError::Illegal));
+        assert_eq!(chess.parse_san(""), Err(NotationError::Syntax));
+    }
+}
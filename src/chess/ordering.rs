@@ -0,0 +1,136 @@
+use std::cmp::Reverse;
+
+use crate::chess::{
+    bitboard,
+    chess::{Chess, Move},
+};
+
+/// The score of the best move of an earlier search of the position.
+const BEST: u32 = u32::MAX;
+/// The lowest score of a capture or promotion.
+const CAPTURE: u32 = 1 << 30;
+/// The score of the second killer move, the first one scores one more.
+const KILLER: u32 = 1 << 29;
+
+/// Sorts the moves of a position, so the moves that are most likely the best are searched
+/// first. The sooner alpha-beta pruning finds a good move, the more of the other moves it can
+/// cut off. The moves are tried in this order:
+///
+/// 1. The best move of an earlier search of the position.
+/// 2. Captures and promotions, the most valuable victim first, and of those the least valuable
+///    attacker first (MVV-LVA).
+/// 3. The killer moves: quiet moves that caused a cut off in another position after the same
+///    number of moves.
+/// 4. The other quiet moves, the moves that caused the most cut offs in the search so far first
+///    (the history heuristic).
+pub struct MoveOrder {
+    /// The last two killer moves for each number of moves played in the game.
+    killers: Vec<[Option<Move>; 2]>,
+    /// How many cut offs each move from a square to a square caused, for each color. Cut offs
+    /// deep in the search count less than cut offs close to the searched position.
+    history: Vec<u32>,
+}
+
+impl MoveOrder {
+    /// Sorts the moves of the given position, the given best move of an earlier search first.
+    pub fn sort(&self, chess: &Chess, moves: &mut [Move], best: Option<Move>) {
+        moves.sort_by_key(|m| Reverse(self.score(chess, *m, best)));
+    }
+
+    /// Remembers that the given quiet move caused a cut off in the given position, which was
+    /// searched the given number of moves deep.
+    pub fn cut_off(&mut self, chess: &Chess, m: Move, depth: u8) {
+        if !is_quiet(chess, m) {
+            return;
+        }
+        let ply = chess.history.len();
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        let index = Self::history_index(chess, m);
+        self.history[index] = self.history[index].saturating_add(depth as u32 * depth as u32);
+    }
+
+    fn score(&self, chess: &Chess, m: Move, best: Option<Move>) -> u32 {
+        if Some(m) == best {
+            return BEST;
+        }
+        if !is_quiet(chess, m) {
+            // The king has no value, which is right for an attacker: it can only capture pieces
+            // that are not defended.
+            let victim = chess
+                .captured_pos(m)
+                .and_then(|pos| chess[pos])
+                .map_or(0, |piece| piece.kind.base_value());
+            let promoted = m.promotion.map_or(0, |kind| kind.base_value() - 1);
+            let attacker = chess[m.from].map_or(0, |piece| piece.kind.base_value());
+            return CAPTURE + 16 * (victim + promoted) as u32 - attacker as u32;
+        }
+        match self.killers.get(chess.history.len()) {
+            Some(killers) if killers[0] == Some(m) => KILLER + 1,
+            Some(killers) if killers[1] == Some(m) => KILLER,
+            _ => self.history[Self::history_index(chess, m)].min(KILLER - 1),
+        }
+    }
+
+    fn history_index(chess: &Chess, m: Move) -> usize {
+        chess.turn.king_index() * 64 * 64 + bitboard::square(m.from) * 64 + bitboard::square(m.to)
+    }
+}
+
+impl Default for MoveOrder {
+    fn default() -> Self {
+        MoveOrder {
+            killers: Vec::new(),
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+}
+
+/// Checks whether a move neither captures a piece nor promotes a pawn, so it does not change the
+/// material on the board.
+pub fn is_quiet(chess: &Chess, m: Move) -> bool {
+    m.promotion.is_none() && chess.captured_pos(m).is_none()
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Move},
+        ordering::*,
+    };
+
+    fn sorted(chess: &Chess, order: &MoveOrder, best: Option<Move>) -> Vec<String> {
+        let mut moves: Vec<Move> = chess.moves().collect();
+        order.sort(chess, &mut moves, best);
+        moves.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn test_move_order() {
+        // White can capture the rook with the queen or the pawn, and the knight with the queen.
+        let chess = Chess::from_fen("4k3/8/8/8/n2r4/4P3/8/3QK3 w - - 0 1").unwrap();
+        let mut order = MoveOrder::default();
+        let moves = sorted(&chess, &order, None);
+        assert_eq!(moves[..3], ["e3d4", "d1d4", "d1a4"]);
+
+        let best = chess.parse_uci("d1a4").unwrap();
+        assert_eq!(sorted(&chess, &order, Some(best))[0], "d1a4");
+
+        // A quiet move that caused a cut off comes right after the captures.
+        let killer = chess.parse_uci("e1f2").unwrap();
+        order.cut_off(&chess, killer, 1);
+        assert_eq!(sorted(&chess, &order, None)[3], "e1f2");
+        // A capture does not become a killer move.
+        order.cut_off(&chess, best, 1);
+        assert_eq!(sorted(&chess, &order, None)[3], "e1f2");
+        assert!(is_quiet(&chess, killer));
+        assert!(!is_quiet(&chess, best));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::chess::chess::{Chess, Move};
+
+impl Chess {
+    /// Counts the move paths of exactly the given number of moves from this position, which is
+    /// known as perft. Draws by the rules do not end a path. Comparing the count with published
+    /// numbers shows whether all legal moves are generated, and nothing more.
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves: Vec<Move> = self.moves().collect();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|m| {
+                self.perform(m);
+                let nodes = self.perft(depth - 1);
+                self.undo();
+                nodes
+            })
+            .sum()
+    }
+
+    /// Splits the [`perft`] count by the first move. A count that differs from a trusted one can
+    /// be narrowed down by following the move whose count is off.
+    ///
+    /// [`perft`]: #method.perft
+    pub fn divide(&mut self, depth: u8) -> Vec<(Move, u64)> {
+        let moves: Vec<Move> = self.moves().collect();
+        moves
+            .into_iter()
+            .map(|m| {
+                self.perform(m);
+                let nodes = self.perft(depth.saturating_sub(1));
+                self.undo();
+                (m, nodes)
+            })
+            .collect()
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{chess::Chess, fen::START_FEN};
+
+    /// Checks the perft counts of a position for the depths 1 and up. The positions and their
+    /// counts are the ones published on the Chess Programming Wiki.
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let mut chess = Chess::from_fen(fen).unwrap();
+        for (depth, count) in (1..).zip(counts) {
+            assert_eq!(chess.perft(depth), *count, "depth {depth} of {fen}");
+        }
+        // All moves have been taken back.
+        assert_eq!(chess.to_fen(), Chess::from_fen(fen).unwrap().to_fen());
+    }
+
+    #[test]
+    fn test_perft_start() {
+        assert_perft(START_FEN, &[20, 400, 8_902, 197_281]);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        // Castling, en passant, promotions and pins, all at once.
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2_039, 97_862],
+        );
+    }
+
+    #[test]
+    fn test_perft_endgame() {
+        // En passant captures that would expose the king to the rook.
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2_812, 43_238],
+        );
+    }
+
+    #[test]
+    fn test_perft_promotions() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9_467],
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1_486, 62_379],
+        );
+    }
+
+    #[test]
+    fn test_perft_chess960() {
+        // Castling with the rooks on other columns, written in Shredder-FEN.
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12_189],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18_002],
+        );
+    }
+
+    #[test]
+    fn test_perft_middlegame() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2_079, 89_890],
+        );
+    }
+
+    #[test]
+    fn test_divide() {
+        let mut chess = Chess::default();
+        let divided = chess.divide(2);
+        assert_eq!(divided.len(), 20);
+        assert!(divided.iter().all(|(_, nodes)| *nodes == 20));
+    }
+}
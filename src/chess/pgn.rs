@@ -0,0 +1,507 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chess::{
+    chess::{Chess, Color, Draw, Move, Outcome},
+    fen::FenError,
+    variant::Rules,
+};
+
+/// The longest line written in the movetext of a PGN.
+const LINE_LENGTH: usize = 80;
+/// The value of the Termination tag of a game that was lost on time.
+const TIME_FORFEIT: &str = "time forfeit";
+/// The value of the Termination tag of a game that was resigned.
+const RESIGNATION: &str = "resignation";
+/// The value of the Variant tag of a game of Chess960 under the rules of standard chess.
+const CHESS960: &str = "Chess960";
+
+/// A move that has been played, together with how it is written in SAN.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlayedMove {
+    pub m: Move,
+    pub san: String,
+}
+
+/// Record of a game: the players, the position it started from, all moves that have been played
+/// and, once the game is over, its outcome.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    /// Date on which the game was played, written as `YYYY.MM.DD`.
+    pub date: String,
+    pub start: Chess,
+    pub moves: Vec<PlayedMove>,
+    pub outcome: Option<Outcome>,
+}
+
+/// The reasons why a string can not be read as a PGN.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PgnError {
+    /// A tag pair is not written as `[Name "value"]`, or a comment or variation is not closed.
+    Syntax,
+    /// The position in the FEN tag can not be read.
+    Fen(FenError),
+    /// The Variant tag names a variant that can not be played.
+    Variant(String),
+    /// A move in the movetext is not legal in the position it is played in.
+    IllegalMove(String),
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::Syntax => write!(f, "invalid PGN syntax"),
+            PgnError::Fen(error) => write!(f, "invalid FEN tag: {error}"),
+            PgnError::Variant(error) => write!(f, "invalid Variant tag: {error}"),
+            PgnError::IllegalMove(san) => write!(f, "illegal move {san}"),
+        }
+    }
+}
+
+impl GameRecord {
+    /// Starts the record of a new game from the given position, played today.
+    pub fn new(start: Chess, white: &str, black: &str) -> Self {
+        GameRecord {
+            white: white.to_owned(),
+            black: black.to_owned(),
+            date: today(),
+            start,
+            moves: vec![],
+            outcome: None,
+        }
+    }
+
+    /// Adds a move to the record. The move is written in SAN using the position before the move.
+    pub fn push(&mut self, before: &Chess, m: Move) {
+        self.moves.push(PlayedMove {
+            m,
+            san: before.san(m),
+        });
+    }
+
+    /// Removes the last move from the record, together with the outcome it may have led to.
+    pub fn undo(&mut self) -> Option<PlayedMove> {
+        self.outcome = None;
+        self.moves.pop()
+    }
+
+    /// The result of the game as written in a PGN.
+    pub fn result(&self) -> &'static str {
+        match self.outcome.map(|outcome| outcome.winner()) {
+            Some(Some(Color::White)) => "1-0",
+            Some(Some(Color::Black)) => "0-1",
+            Some(None) => "1/2-1/2",
+            None => "*",
+        }
+    }
+
+    /// Writes the game in Portable Game Notation. A game that did not start from the initial
+    /// position gets its starting position in a FEN tag, a game of another variant than standard
+    /// chess or of Chess960 gets a Variant tag, a game lost on time or resigned gets a
+    /// Termination tag, and a drawn or resigned game gets the reason in a comment before the
+    /// result.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        let mut tags = vec![
+            ("Event", "Automated chessboard game".to_owned()),
+            ("Site", "?".to_owned()),
+            ("Date", self.date.clone()),
+            ("Round", "-".to_owned()),
+            ("White", self.white.clone()),
+            ("Black", self.black.clone()),
+            ("Result", self.result().to_owned()),
+        ];
+        match self.outcome {
+            Some(Outcome::OutOfTime(_)) => tags.push(("Termination", TIME_FORFEIT.to_owned())),
+            Some(Outcome::Resigned(_)) => tags.push(("Termination", RESIGNATION.to_owned())),
+            _ => {}
+        }
+        // The FEN of a Chess960 game under other rules tells by its castling rights that the
+        // rooks may start anywhere.
+        if self.start.rules != Rules::Standard {
+            tags.push(("Variant", self.start.rules.to_string()));
+        } else if self.start.chess960 {
+            tags.push(("Variant", CHESS960.to_owned()));
+        }
+        // Without castling in the variant, its start position has no castling rights.
+        let mut standard = Chess::default();
+        standard.set_rules(self.start.rules);
+        if self.start.to_fen() != standard.to_fen() {
+            tags.push(("SetUp", "1".to_owned()));
+            tags.push(("FEN", self.start.to_fen()));
+        }
+        for (name, value) in tags {
+            pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+        pgn.push('\n');
+
+        let mut tokens = vec![];
+        let black_first = self.start.turn == Color::Black;
+        let first_number = self.start.fullmove_number as usize;
+        if black_first && !self.moves.is_empty() {
+            tokens.push(format!("{first_number}..."));
+        }
+        let mut turn = self.start.turn;
+        for (index, played) in self.moves.iter().enumerate() {
+            // A game that starts with black's move is one ply ahead.
+            let number = (index + black_first as usize) / 2 + first_number;
+            if turn == Color::White {
+                tokens.push(format!("{number}."));
+            }
+            tokens.push(played.san.clone());
+            turn = !turn;
+        }
+        if let Some(reason @ (Outcome::Stalemate | Outcome::Draw(_) | Outcome::Resigned(_))) =
+            self.outcome
+        {
+            tokens.push(format!("{{{reason}}}"));
+        }
+        tokens.push(self.result().to_owned());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    /// Writes the game as a PGN file.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_pgn())
+    }
+
+    /// Reads the first game of a PGN. Comments, variations and numeric annotations in the
+    /// movetext are skipped.
+    pub fn from_pgn(pgn: &str) -> Result<GameRecord, PgnError> {
+        let mut game = GameRecord {
+            date: "????.??.??".to_owned(),
+            ..GameRecord::new(Chess::default(), "?", "?")
+        };
+        let mut rules = Rules::Standard;
+        let mut chess960 = false;
+        let mut fen = None;
+        let mut termination = String::new();
+        let mut movetext = String::new();
+        let mut in_movetext = false;
+        for line in pgn.lines() {
+            let line = line.trim();
+            if line.starts_with('[') && !in_movetext {
+                let (name, value) = parse_tag(line).ok_or(PgnError::Syntax)?;
+                match name {
+                    "White" => game.white = value.to_owned(),
+                    "Black" => game.black = value.to_owned(),
+                    "Date" => game.date = value.to_owned(),
+                    "FEN" => fen = Some(value.to_owned()),
+                    "Variant" if value.eq_ignore_ascii_case(CHESS960) => chess960 = true,
+                    "Variant" => rules = value.parse().map_err(PgnError::Variant)?,
+                    "Termination" => termination = value.to_owned(),
+                    _ => {}
+                }
+            } else if !line.is_empty() {
+                in_movetext = true;
+                movetext.push_str(line);
+                movetext.push(' ');
+            } else if in_movetext {
+                // The empty line after the movetext ends the first game.
+                break;
+            }
+        }
+        // The FEN tag may come before the Variant tag, but is read by the rules of the variant.
+        match fen {
+            Some(fen) => {
+                game.start = Chess::from_fen_with_rules(&fen, rules).map_err(PgnError::Fen)?
+            }
+            None => game.start.set_rules(rules),
+        }
+        game.start.chess960 |= chess960;
+
+        let mut chess = game.start.clone();
+        for token in movetext_tokens(&movetext)? {
+            match token {
+                "1-0" => game.outcome = Some(Outcome::Winner(Color::White)),
+                "0-1" => game.outcome = Some(Outcome::Winner(Color::Black)),
+                // A draw that the rules do not explain in the final position was agreed upon.
+                "1/2-1/2" => {
+                    game.outcome = match chess.outcome() {
+                        Some(draw @ (Outcome::Stalemate | Outcome::Draw(_))) => Some(draw),
+                        _ => Some(Outcome::Draw(Draw::Agreement)),
+                    }
+                }
+                "*" => game.outcome = None,
+                san => {
+                    let m = chess
+                        .parse_san(san)
+                        .map_err(|_| PgnError::IllegalMove(san.to_owned()))?;
+                    game.push(&chess, m);
+                    chess.perform(m);
+                }
+            }
+        }
+        if let Some(Outcome::Winner(color)) = game.outcome {
+            if termination.eq_ignore_ascii_case(TIME_FORFEIT) {
+                game.outcome = Some(Outcome::OutOfTime(!color));
+            } else if termination.eq_ignore_ascii_case(RESIGNATION) {
+                game.outcome = Some(Outcome::Resigned(!color));
+            }
+        }
+        Ok(game)
+    }
+}
+
+/// Reads a tag pair like `[White "Magnus"]` as its name and value.
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name, value))
+}
+
+/// Splits the movetext into moves and results, leaving out move numbers, comments, variations
+/// and numeric annotations.
+fn movetext_tokens(movetext: &str) -> Result<Vec<&str>, PgnError> {
+    let mut tokens = vec![];
+    let mut depth = 0;
+    let mut in_comment = false;
+    let mut start = None;
+    for (index, c) in movetext.char_indices() {
+        let separator = c.is_whitespace() || matches!(c, '{' | '}' | '(' | ')');
+        if separator {
+            if let Some(from) = start.take() {
+                if !in_comment && depth == 0 {
+                    tokens.push(&movetext[from..index]);
+                }
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+        match c {
+            '{' if !in_comment => in_comment = true,
+            '}' if in_comment => in_comment = false,
+            '(' if !in_comment => depth += 1,
+            ')' if !in_comment => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            return Err(PgnError::Syntax);
+        }
+    }
+    if let Some(from) = start {
+        tokens.push(&movetext[from..]);
+    }
+    if in_comment || depth != 0 {
+        return Err(PgnError::Syntax);
+    }
+    // Move numbers end with a dot and can be attached to the move, numeric annotations start with
+    // a dollar sign.
+    Ok(tokens
+        .into_iter()
+        .map(|token| {
+            let number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+            if number.starts_with('.') {
+                number.trim_start_matches('.')
+            } else {
+                token
+            }
+        })
+        .filter(|token| !token.is_empty() && !token.starts_with('$'))
+        .collect())
+}
+
+/// Today's date as written in a PGN, `YYYY.MM.DD`.
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+/// Converts a number of days since 1970-01-01 to a year, month and day in the Gregorian
+/// calendar, using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Color, Draw, Outcome},
+        pgn::*,
+    };
+
+    /// Records a list of moves in SAN from the given position.
+    fn record(start: Chess, moves: &[&str]) -> GameRecord {
+        let mut game = GameRecord::new(start.clone(), "Human", "Computer");
+        let mut chess = start;
+        for san in moves {
+            let m = chess.parse_san(san).unwrap();
+            game.push(&chess, m);
+            chess.perform(m);
+        }
+        game
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let mut game = record(Chess::default(), &["f3", "e5", "g4", "Qh4#"]);
+        game.date = "2024.05.01".to_owned();
+        game.outcome = Some(Outcome::Winner(Color::Black));
+        assert_eq!(
+            game.to_pgn(),
+            "[Event \"Automated chessboard game\"]\n\
+             [Site \"?\"]\n\
+             [Date \"2024.05.01\"]\n\
+             [Round \"-\"]\n\
+             [White \"Human\"]\n\
+             [Black \"Computer\"]\n\
+             [Result \"0-1\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let start = Chess::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        let game = record(start, &["Kd7", "e4", "Kc6"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]"));
+        assert!(pgn.contains("1... Kd7 2. e4 Kc6 *"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+    }
+
+    #[test]
+    fn test_from_pgn() {
+        let pgn = "[Event \"Casual\"]\n\
+                   [White \"Anderssen\"]\n\
+                   [Black \"Kieseritzky\"]\n\
+                   [Result \"1-0\"]\n\
+                   \n\
+                   1. e4 {The king's pawn} e5 2. f4 (2. Nf3 Nc6) exf4 $1 3. Bc4\n\
+                   Qh4+ 4. Kf1 1-0\n";
+        let game = GameRecord::from_pgn(pgn).unwrap();
+        assert_eq!(game.white, "Anderssen");
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(game.moves[6].san, "Kf1");
+        assert_eq!(game.outcome, Some(Outcome::Winner(Color::White)));
+
+        assert_eq!(
+            GameRecord::from_pgn("1. e4 e5 2. Ke3"),
+            Err(PgnError::IllegalMove("Ke3".to_owned()))
+        );
+        assert_eq!(GameRecord::from_pgn("1. e4 {open"), Err(PgnError::Syntax));
+    }
+
+    #[test]
+    fn test_variant() {
+        let mut start = Chess::default();
+        start.set_rules(Rules::Antichess);
+        let game = record(start, &["e3", "b5", "Bxb5"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Variant \"Antichess\"]"));
+        assert!(!pgn.contains("[FEN"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+
+        // Captures are forced, so white has to take the pawn.
+        let pgn = "[Variant \"Antichess\"]\n\n1. e3 b5 2. Nc3";
+        assert_eq!(
+            GameRecord::from_pgn(pgn),
+            Err(PgnError::IllegalMove("Nc3".to_owned()))
+        );
+        assert!(matches!(
+            GameRecord::from_pgn("[Variant \"Crazyhouse\"]\n\n1. e4"),
+            Err(PgnError::Variant(_))
+        ));
+
+        // The FEN is read by the rules of the variant, even when the Variant tag comes after it.
+        let pgn = "[FEN \"8/8/8/8/8/8/3p4/4K3 w - - 0 1\"]\n[Variant \"Antichess\"]\n\n1. Kxd2";
+        let game = GameRecord::from_pgn(pgn).unwrap();
+        assert_eq!(game.start.rules, Rules::Antichess);
+        assert_eq!(game.outcome, None);
+        assert_eq!(game.moves[0].san, "Kxd2");
+    }
+
+    #[test]
+    fn test_time_forfeit() {
+        let mut game = record(Chess::default(), &["e4", "e5"]);
+        game.outcome = Some(Outcome::OutOfTime(Color::White));
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Result \"0-1\"]\n[Termination \"time forfeit\"]"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+    }
+
+    #[test]
+    fn test_draw_reason() {
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        let mut game = record(Chess::default(), &shuffle.repeat(2));
+        game.outcome = Some(Outcome::Draw(Draw::Repetition));
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Result \"1/2-1/2\"]"));
+        assert!(pgn.ends_with("4. Ng1 Ng8 {Draw by threefold repetition}\n1/2-1/2\n"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+
+        // Draws that do not follow from the rules were agreed upon.
+        let game = GameRecord::from_pgn("1. e4 e5 1/2-1/2").unwrap();
+        assert_eq!(game.outcome, Some(Outcome::Draw(Draw::Agreement)));
+        assert!(game
+            .to_pgn()
+            .ends_with("1. e4 e5 {Draw by agreement} 1/2-1/2\n"));
+
+        let mut game = record(Chess::default(), &["e4", "e5"]);
+        game.outcome = Some(Outcome::Resigned(Color::Black));
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Result \"1-0\"]\n[Termination \"resignation\"]"));
+        assert!(pgn.ends_with("1. e4 e5 {Black resigned} 1-0\n"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+    }
+
+    #[test]
+    fn test_chess960() {
+        let game = record(Chess::chess960(0), &["Nf3", "g6"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Variant \"Chess960\"]"));
+        assert!(pgn.contains("[FEN \"bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1\"]"));
+        let read = GameRecord::from_pgn(&pgn).unwrap();
+        assert!(read.start.chess960);
+        assert_eq!(read, game);
+
+        // The classical start position only castles like Chess960 with the Variant tag.
+        let game = record(Chess::chess960(518), &["e4", "e5"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Variant \"Chess960\"]"));
+        assert!(!pgn.contains("[FEN"));
+        assert_eq!(GameRecord::from_pgn(&pgn), Ok(game));
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
 
 ///Position on the chessboard
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -29,9 +30,34 @@ impl Pos {
     }
 }
 
+/// Writes the position as the name of the square, for example `e4`.
 impl Display for Pos {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", (self.y as u8 + 65) as char, 8 - self.x)
+        write!(f, "{}{}", (self.x as u8 + b'a') as char, self.y + 1)
+    }
+}
+
+/// Error returned when a string is not the name of a square on the board.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParsePosError;
+
+impl Display for ParsePosError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "not the name of a square on the board")
+    }
+}
+
+/// Reads the name of a square, for example `e4`, as a position on the board.
+impl FromStr for Pos {
+    type Err = ParsePosError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Ok(Pos::new((file - b'a') as isize, (rank - b'1') as isize))
+            }
+            _ => Err(ParsePosError),
+        }
     }
 }
 
@@ -129,6 +155,14 @@ impl Shift {
         Shift { dx: -2, dy: -1 },
         Shift { dx: -1, dy: -2 },
     ];
+
+    pub fn dx(&self) -> isize {
+        self.dx
+    }
+
+    pub fn dy(&self) -> isize {
+        self.dy
+    }
 }
 
 impl Neg for Shift {
@@ -0,0 +1,165 @@
+use std::{
+    fmt::{Display, Formatter},
+    ops::Neg,
+};
+
+use crate::chess::chess::Color;
+
+/// The score of a position in the search of the chess computer. A positive score means that white
+/// is better off, and a negative score that black is.
+///
+/// A score is either the score an [`Evaluator`] gives a position, or a forced mate. A mate is
+/// scored higher than any evaluation, and counts how many moves away it is: a mate in 1 scores
+/// higher than a mate in 5, so the search plays the shortest mate, and the player that gets mated
+/// plays the longest defence.
+///
+/// The distance to a mate is counted from the position the score belongs to. So when the score
+/// of a position is used for the position before it, the mate is one move further away, see
+/// [`parent`].
+///
+/// [`Evaluator`]: crate::chess::evaluation::Evaluator
+/// [`parent`]: #method.parent
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Score(i16);
+
+impl Score {
+    /// Higher than any score. Used with its negative as the bounds of a search that has not found
+    /// anything yet.
+    pub const INFINITY: Score = Score(i16::MAX);
+    pub const DRAW: Score = Score(0);
+    /// The score of a position in which black is checkmated.
+    const MATE: i16 = 30_000;
+    /// The most moves of both players a mate can be away from a position.
+    const MAX_MATE_PLIES: i16 = 1_000;
+    /// The highest score an evaluation can get, below all mate scores.
+    const MAX_EVALUATION: i16 = Self::MATE - Self::MAX_MATE_PLIES - 1;
+
+    /// The score of an evaluation of a position. Evaluations that are too high to be told apart
+    /// from a mate are lowered.
+    pub fn evaluation(evaluation: i16) -> Score {
+        Score(evaluation.clamp(-Self::MAX_EVALUATION, Self::MAX_EVALUATION))
+    }
+
+    /// The score of a position in which the player of the given color is checkmated.
+    pub fn checkmated(color: Color) -> Score {
+        match color {
+            Color::Black => Score(Self::MATE),
+            Color::White => Score(-Self::MATE),
+        }
+    }
+
+    /// The score of a position in which the player of the given color gets checkmated after the
+    /// given number of moves of both players.
+    pub fn checkmated_after(color: Color, plies: i16) -> Score {
+        let plies = plies.clamp(0, Self::MAX_MATE_PLIES);
+        let mated = Self::checkmated(color);
+        Score(mated.0 - plies * mated.0.signum())
+    }
+
+    /// Returns in how many moves of the winning player the game ends in a mate, if it does. The
+    /// number is negative when black is the one that mates.
+    pub fn mate_in(&self) -> Option<i16> {
+        if !self.is_mate() {
+            return None;
+        }
+        let moves = (Self::MATE - self.0.abs() + 1) / 2;
+        Some(moves * self.0.signum())
+    }
+
+    /// Returns in how many moves of both players the game ends in a mate, if it does.
+    pub fn mate_plies(&self) -> Option<i16> {
+        self.is_mate().then_some(Self::MATE - self.0.abs())
+    }
+
+    /// Returns the evaluation this score was made from, if it is not a mate.
+    pub fn value(&self) -> Option<i16> {
+        (self.0.abs() <= Self::MAX_EVALUATION).then_some(self.0)
+    }
+
+    /// Returns the score of the position before the position this score belongs to, in which the
+    /// mate is one move further away.
+    pub fn parent(self) -> Score {
+        if self.is_mate() {
+            Score(self.0 - self.0.signum())
+        } else {
+            self
+        }
+    }
+
+    /// Returns the score of a position after the move to it, in which the mate is one move
+    /// closer. This is the opposite of [`parent`], used to pass the bounds of alpha-beta pruning
+    /// to the positions after the moves.
+    ///
+    /// [`parent`]: #method.parent
+    pub fn child(self) -> Score {
+        if self.is_mate() {
+            Score(self.0 + self.0.signum())
+        } else {
+            self
+        }
+    }
+
+    fn is_mate(&self) -> bool {
+        self.0.abs() > Self::MAX_EVALUATION && *self != Self::INFINITY && *self != -Self::INFINITY
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Score(-self.0)
+    }
+}
+
+/// Writes a mate as `#` followed by the number of moves, for example `#3` or `#-2` when black
+/// mates, and an evaluation as its value.
+impl Display for Score {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.mate_in() {
+            Some(moves) => write!(f, "#{moves}"),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{chess::Color, score::*};
+
+    #[test]
+    fn test_mate_score() {
+        let mated = Score::checkmated(Color::Black);
+        assert_eq!(mated.mate_in(), Some(0));
+        assert_eq!(mated.value(), None);
+        // White mates with its next move, and black can delay it by one move.
+        let mate_in_1 = mated.parent();
+        let mate_in_2 = mate_in_1.parent().parent();
+        assert_eq!(mate_in_1.mate_in(), Some(1));
+        assert_eq!(mate_in_2.mate_in(), Some(2));
+        assert_eq!(mate_in_2.mate_plies(), Some(3));
+        assert_eq!(mate_in_2.child().child(), mate_in_1);
+        assert!(mate_in_1 > mate_in_2);
+        assert_eq!(Score::checkmated_after(Color::Black, 3), mate_in_2);
+        assert!(mate_in_2 > Score::evaluation(i16::MAX));
+        assert!(Score::INFINITY > mated);
+
+        let black_mates = Score::checkmated(Color::White).parent();
+        assert_eq!(black_mates.mate_in(), Some(-1));
+        assert!(black_mates < -mate_in_2);
+        assert_eq!(black_mates.to_string(), "#-1");
+    }
+
+    #[test]
+    fn test_evaluation_score() {
+        let score = Score::evaluation(150);
+        assert_eq!(score.mate_in(), None);
+        assert_eq!(score.mate_plies(), None);
+        assert_eq!(score.value(), Some(150));
+        assert_eq!(score.parent(), score);
+        assert_eq!(score.to_string(), "150");
+        assert!(Score::evaluation(i16::MIN) > Score::checkmated(Color::White));
+        assert_eq!(Score::INFINITY.parent(), Score::INFINITY);
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::chess::{
+    chess::{Chess, Color, Move, Outcome},
+    computer::{search, Budget, Limit},
+    evaluation::{Material, Positional},
+    pgn::GameRecord,
+    transposition::TranspositionTable,
+};
+
+/// How much wider than the standard error the error bar of the Elo difference is, for a
+/// confidence of 95%.
+const CONFIDENCE: f64 = 1.96;
+
+/// The [`Evaluator`] an engine in a match searches with.
+///
+/// [`Evaluator`]: crate::chess::evaluation::Evaluator
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Evaluation {
+    Material,
+    Positional,
+}
+
+/// A configuration of the chess computer that plays in a match: how it evaluates positions and
+/// how long it searches for each move. It is written as the evaluation and the budget separated
+/// by a colon, for example `positional:depth=4`, `material:nodes=20000` or `positional:time=200`,
+/// with the time in milliseconds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Engine {
+    pub evaluation: Evaluation,
+    pub budget: Budget,
+}
+
+impl Engine {
+    /// Searches the best move in the given position, or returns `None` when the game is over.
+    fn best_move(&self, chess: &mut Chess, table: &mut TranspositionTable) -> Option<Move> {
+        let limit = Limit::new(self.budget);
+        match self.evaluation {
+            Evaluation::Material => search::<Material>(chess, table, limit).m,
+            Evaluation::Positional => search::<Positional>(chess, table, limit).m,
+        }
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || format!("unknown engine \"{s}\", use for example positional:depth=4");
+        let (evaluation, budget) = s.split_once(':').ok_or_else(usage)?;
+        let evaluation = match evaluation.to_ascii_lowercase().as_str() {
+            "material" => Evaluation::Material,
+            "positional" => Evaluation::Positional,
+            _ => return Err(usage()),
+        };
+        let (kind, amount) = budget.split_once('=').ok_or_else(usage)?;
+        let amount: u64 = amount.parse().map_err(|_| usage())?;
+        let budget = match kind.to_ascii_lowercase().as_str() {
+            "depth" => Budget::Depth(amount.clamp(1, u8::MAX as u64) as u8),
+            "nodes" => Budget::Nodes(amount),
+            "time" => Budget::Time(Duration::from_millis(amount)),
+            _ => return Err(usage()),
+        };
+        Ok(Engine { evaluation, budget })
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.evaluation {
+            Evaluation::Material => write!(f, "material:")?,
+            Evaluation::Positional => write!(f, "positional:")?,
+        }
+        match self.budget {
+            Budget::Depth(depth) => write!(f, "depth={depth}"),
+            Budget::Nodes(nodes) => write!(f, "nodes={nodes}"),
+            Budget::Time(time) => write!(f, "time={}", time.as_millis()),
+        }
+    }
+}
+
+/// The results of a match, from the side of the first engine.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Results {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Results {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The part of the points the first engine scored, a win counting as one point and a draw
+    /// as half a point.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Estimates how many Elo points the first engine is stronger than the second, from the
+    /// score of the match. Returns the difference together with the error bar of its 95%
+    /// confidence interval. When one of the engines scored all points the difference can not be
+    /// estimated, and `None` is returned.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if !(score > 0.0 && score < 1.0) {
+            return None;
+        }
+        let games = self.games() as f64;
+        let deviation = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let error = CONFIDENCE * (deviation / games).sqrt();
+        let margin = (elo(score + error) - elo(score - error)) / 2.0;
+        Some((elo(score), margin))
+    }
+
+    /// Counts the outcome of a game in which the first engine played with the given color.
+    fn add(&mut self, outcome: Option<Outcome>, first: Color) {
+        match outcome.and_then(|outcome| outcome.winner()) {
+            Some(color) if color == first => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+/// Writes the results as wins, draws and losses, followed by the score and the estimated Elo
+/// difference, for example `+12 =20 -8, 55.0%, Elo +35 ± 60`.
+impl Display for Results {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)?;
+        if self.games() == 0 {
+            return Ok(());
+        }
+        write!(f, ", {:.1}%", 100.0 * self.score())?;
+        match self.elo() {
+            Some((difference, margin)) => write!(f, ", Elo {difference:+.0} ± {margin:.0}"),
+            None => write!(f, ", Elo unknown"),
+        }
+    }
+}
+
+/// The Elo difference at which a player is expected to get the given part of the points.
+fn elo(score: f64) -> f64 {
+    let score = score.clamp(f64::EPSILON, 1.0 - f64::EPSILON);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// A match between two engines, which play the openings in turn. Every opening is played twice,
+/// once with the first engine as white and once with the second engine as white, so neither
+/// engine is favoured by the openings.
+pub struct Match {
+    pub first: Engine,
+    pub second: Engine,
+    pub openings: Vec<Chess>,
+    pub games: u32,
+}
+
+impl Match {
+    /// Plays all games of the match one after another, and returns the results. After every
+    /// game `on_game` is called with its record and the results so far.
+    pub fn play(&self, mut on_game: impl FnMut(&GameRecord, &Results)) -> Results {
+        let mut results = Results::default();
+        for game in 0..self.games {
+            let opening = &self.openings[(game / 2) as usize % self.openings.len()];
+            let first = match game % 2 {
+                0 => Color::White,
+                _ => Color::Black,
+            };
+            let record = match first {
+                Color::White => play_game(&self.first, &self.second, opening),
+                Color::Black => play_game(&self.second, &self.first, opening),
+            };
+            results.add(record.outcome, first);
+            on_game(&record, &results);
+        }
+        results
+    }
+}
+
+/// Plays a game between two engines from the given position until it is over. Both engines
+/// start the game with an empty [`TranspositionTable`] of their own.
+pub fn play_game(white: &Engine, black: &Engine, start: &Chess) -> GameRecord {
+    let mut record = GameRecord::new(start.clone(), &white.to_string(), &black.to_string());
+    let mut tables = [TranspositionTable::default(), TranspositionTable::default()];
+    let mut chess = start.clone();
+    while chess.outcome().is_none() {
+        let (engine, table) = match chess.turn {
+            Color::White => (white, &mut tables[0]),
+            Color::Black => (black, &mut tables[1]),
+        };
+        let Some(m) = engine.best_move(&mut chess, table) else {
+            break;
+        };
+        record.push(&chess, m);
+        chess.perform(m);
+    }
+    record.outcome = chess.outcome();
+    record
+}
+
+/// Reads the openings of a match, one position on each line as a FEN or an EPD. The operations
+/// of an EPD, like `bm e4;`, are ignored, as are empty lines and lines starting with `#`.
+pub fn read_openings(text: &str) -> Result<Vec<Chess>, String> {
+    let mut openings = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // A FEN ends with the two move clocks, which an EPD does not have.
+        let length = match fields.get(4..6) {
+            Some(clocks) if clocks.iter().all(|clock| clock.parse::<u16>().is_ok()) => 6,
+            _ => fields.len().min(4),
+        };
+        let chess = Chess::from_fen(&fields[..length].join(" "))
+            .map_err(|error| format!("line {}: {error}", number + 1))?;
+        openings.push(chess);
+    }
+    Ok(openings)
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{fen::START_FEN, selfplay::*};
+
+    #[test]
+    fn test_engine() {
+        let engine: Engine = "Positional:depth=4".parse().unwrap();
+        assert_eq!(engine.evaluation, Evaluation::Positional);
+        assert_eq!(engine.budget, Budget::Depth(4));
+        assert_eq!(engine.to_string(), "positional:depth=4");
+        let engine: Engine = "material:time=200".parse().unwrap();
+        assert_eq!(engine.budget, Budget::Time(Duration::from_millis(200)));
+        assert_eq!(engine.to_string(), "material:time=200");
+
+        assert!("positional".parse::<Engine>().is_err());
+        assert!("random:depth=4".parse::<Engine>().is_err());
+        assert!("material:moves=4".parse::<Engine>().is_err());
+    }
+
+    #[test]
+    fn test_elo() {
+        let even = Results {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        };
+        let (difference, margin) = even.elo().unwrap();
+        assert!(difference.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        // Scoring 75% is worth about 191 Elo, and more games make the estimate more precise.
+        let ahead = Results {
+            wins: 60,
+            draws: 30,
+            losses: 10,
+        };
+        let (difference, margin) = ahead.elo().unwrap();
+        assert_eq!(difference.round(), 191.0);
+        let more_games = Results {
+            wins: 600,
+            draws: 300,
+            losses: 100,
+        };
+        assert!(more_games.elo().unwrap().1 < margin);
+        assert_eq!(
+            more_games.to_string(),
+            "+600 =300 -100, 75.0%, Elo +191 ± 19"
+        );
+
+        let sweep = Results {
+            wins: 10,
+            ..Default::default()
+        };
+        assert_eq!(sweep.elo(), None);
+    }
+
+    #[test]
+    fn test_read_openings() {
+        let text = "# Openings\n\
+            rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n\
+            \n\
+            rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 bm e5; id \"king pawn\";\n";
+        let openings = read_openings(text).unwrap();
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[0].to_fen(), START_FEN);
+        assert_eq!(openings[1].turn, Color::Black);
+
+        let error = read_openings("8/8/8/8 w - -").unwrap_err();
+        assert!(error.starts_with("line 1:"));
+    }
+
+    #[test]
+    fn test_match() {
+        // White mates in one, which both engines find, so each engine wins the game in which it
+        // plays white.
+        let openings = read_openings("7k/R7/8/8/8/8/1R6/7K w - - 0 1").unwrap();
+        let positional: Engine = "positional:depth=3".parse().unwrap();
+        let material: Engine = "material:depth=1".parse().unwrap();
+        let game_match = Match {
+            first: positional,
+            second: material,
+            openings,
+            games: 2,
+        };
+        let mut records = vec![];
+        let results = game_match.play(|record, _| records.push(record.clone()));
+        assert_eq!(records[0].white, "positional:depth=3");
+        assert_eq!(records[1].white, "material:depth=1");
+        assert_eq!(records[1].outcome, Some(Outcome::Winner(Color::White)));
+        assert_eq!(
+            results,
+            Results {
+                wins: 1,
+                draws: 0,
+                losses: 1,
+            }
+        );
+    }
+}
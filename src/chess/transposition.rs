@@ -0,0 +1,120 @@
+use crate::chess::{chess::Move, score::Score};
+
+/// How the score of a searched position relates to its real score. When the search of a position
+/// is cut off by alpha-beta pruning, only a bound of the real score is known.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bound {
+    /// The score is the real score of the position.
+    Exact,
+    /// The real score is at least the score.
+    Lower,
+    /// The real score is at most the score.
+    Upper,
+}
+
+/// The result of searching a position, stored in the [`TranspositionTable`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    /// The [`Chess::position_key`] of the position.
+    ///
+    /// [`Chess::position_key`]: crate::chess::chess::Chess::position_key
+    pub key: u64,
+    /// How many moves deep the position was searched.
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: Score,
+    /// The best move found, or the move that caused the cut off.
+    pub best: Option<Move>,
+}
+
+/// Remembers the results of searched positions, so the search does not have to search a position
+/// again when it is reached through other moves, or in the search for a later move.
+///
+/// The table has a fixed number of slots and each position has one slot, chosen by its key. When
+/// two positions share a slot, the position that was searched last replaces the other one, unless
+/// it is the same position searched less deep.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    /// The number of slots of the table used by the chess computer.
+    pub const DEFAULT_SIZE: usize = 1 << 16;
+
+    /// Creates an empty table with at least the given number of slots, which is rounded up to a
+    /// power of two.
+    pub fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: vec![None; size.max(1).next_power_of_two()],
+        }
+    }
+
+    /// Returns the stored result of the position with the given key, if any.
+    pub fn get(&self, key: u64) -> Option<Entry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    /// Stores the result of a searched position.
+    pub fn insert(&mut self, entry: Entry) {
+        let index = self.index(entry.key);
+        let slot = &mut self.entries[index];
+        match slot {
+            Some(stored) if stored.key == entry.key && stored.depth > entry.depth => {}
+            _ => *slot = Some(entry),
+        }
+    }
+
+    /// Removes all stored results.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(Self::DEFAULT_SIZE)
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::transposition::*;
+
+    fn entry(key: u64, depth: u8) -> Entry {
+        Entry {
+            key,
+            depth,
+            bound: Bound::Exact,
+            score: Score::evaluation(depth as i16),
+            best: None,
+        }
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut table = TranspositionTable::new(100);
+        assert_eq!(table.entries.len(), 128);
+        assert_eq!(table.get(5), None);
+
+        table.insert(entry(5, 3));
+        assert_eq!(table.get(5), Some(entry(5, 3)));
+        // A shallower search of the same position does not replace the deeper one.
+        table.insert(entry(5, 2));
+        assert_eq!(table.get(5), Some(entry(5, 3)));
+        table.insert(entry(5, 4));
+        assert_eq!(table.get(5), Some(entry(5, 4)));
+
+        // Another position in the same slot replaces it.
+        table.insert(entry(5 + 128, 1));
+        assert_eq!(table.get(5), None);
+        assert_eq!(table.get(5 + 128), Some(entry(5 + 128, 1)));
+
+        table.clear();
+        assert_eq!(table.get(5 + 128), None);
+    }
+}
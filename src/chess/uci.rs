@@ -0,0 +1,406 @@
+use std::{
+    io::{self, BufRead, Write},
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::chess::{
+    chess::{Chess, Color},
+    clock::time_budget,
+    computer::{search, Budget, Limit},
+    evaluation::Positional,
+    score::Score,
+    transposition::{Entry, TranspositionTable},
+};
+
+/// The name the engine gives itself to the user interface.
+const NAME: &str = "Automated chessboard";
+/// The size of the transposition table in megabytes, until the user interface sets the `Hash`
+/// option.
+const DEFAULT_HASH: usize = 4;
+const MAX_HASH: usize = 1024;
+
+/// Runs the chess computer as an engine for chess user interfaces and tournament programs, which
+/// send commands of the Universal Chess Interface (UCI) on the standard input. The answers are
+/// written to the standard output. Runs until the `quit` command, or until the input is closed
+/// and the last search has finished.
+pub fn run() {
+    let mut uci = Uci::new(io::stdout());
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !uci.handle(&line) {
+            return;
+        }
+    }
+    uci.wait();
+}
+
+/// The state of the engine between the commands of the Universal Chess Interface: the position
+/// to search and the search that is running, if any. The search runs on its own thread, so the
+/// engine can answer `isready` and `stop` in the meantime.
+pub struct Uci<W: Write + Send + 'static> {
+    chess: Chess,
+    /// The [`TranspositionTable`] is kept between the searches, until a new game starts.
+    table: Arc<Mutex<TranspositionTable>>,
+    output: Arc<Mutex<W>>,
+    search: Option<Search>,
+    /// Whether the positions are games of Chess960, set with the `UCI_Chess960` option. Castling
+    /// is then written as the king moving onto its own rook.
+    chess960: bool,
+}
+
+/// A search that is running on its own thread.
+struct Search {
+    thread: JoinHandle<()>,
+    /// Stops the search once it is set, after which it still sends its best move.
+    stop: Arc<AtomicBool>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    /// Creates an engine that writes its answers to the given output.
+    pub fn new(output: W) -> Self {
+        Uci {
+            chess: Chess::default(),
+            table: Arc::new(Mutex::new(table_of(DEFAULT_HASH))),
+            output: Arc::new(Mutex::new(output)),
+            search: None,
+            chess960: false,
+        }
+    }
+
+    /// Handles one line of input. Returns `false` when the engine has to quit. Unknown commands
+    /// are ignored, as the protocol asks.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, arguments)) = words.split_first() else {
+            return true;
+        };
+        let result = match *command {
+            "uci" => {
+                self.send(&format!("id name {NAME}"));
+                self.send("id author Sandraak");
+                self.send(&format!(
+                    "option name Hash type spin default {DEFAULT_HASH} min 1 max {MAX_HASH}"
+                ));
+                self.send("option name Clear Hash type button");
+                self.send("option name UCI_Chess960 type check default false");
+                self.send("uciok");
+                Ok(())
+            }
+            "isready" => {
+                self.send("readyok");
+                Ok(())
+            }
+            "ucinewgame" => {
+                self.stop();
+                self.chess = Chess::default();
+                self.table.lock().unwrap().clear();
+                Ok(())
+            }
+            "setoption" => {
+                self.stop();
+                self.set_option(arguments)
+            }
+            "position" => {
+                self.stop();
+                self.position(arguments)
+            }
+            "go" => {
+                self.go(arguments);
+                Ok(())
+            }
+            "stop" => {
+                self.stop();
+                Ok(())
+            }
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => Ok(()),
+        };
+        if let Err(error) = result {
+            self.send(&format!("info string {error}"));
+        }
+        true
+    }
+
+    /// Sets up the position of a `position` command: `startpos` or `fen` followed by a FEN, and
+    /// optionally `moves` followed by the moves played from there in UCI long notation.
+    fn position(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let moves_at = arguments
+            .iter()
+            .position(|word| *word == "moves")
+            .unwrap_or(arguments.len());
+        let mut chess = match &arguments[..moves_at] {
+            ["startpos"] => Chess::default(),
+            ["fen", fen @ ..] => Chess::from_fen(&fen.join(" "))
+                .map_err(|error| format!("could not read the FEN: {error}"))?,
+            _ => return Err("expected startpos or fen in the position command".to_owned()),
+        };
+        chess.chess960 |= self.chess960;
+        for uci in arguments.iter().skip(moves_at + 1) {
+            let m = chess
+                .parse_uci(uci)
+                .map_err(|error| format!("could not play {uci}: {error}"))?;
+            chess.perform(m);
+        }
+        self.chess = chess;
+        Ok(())
+    }
+
+    /// Starts searching the current position for the `go` command. How long the search may take
+    /// is set by `depth`, `movetime`, `nodes`, or by the time left on the clock of the player to
+    /// move. Without any of those, for example with `infinite`, it searches until `stop`.
+    fn go(&mut self, arguments: &[&str]) {
+        self.stop();
+        let number = |name: &str| {
+            let at = arguments.iter().position(|word| *word == name)?;
+            arguments.get(at + 1)?.parse::<u64>().ok()
+        };
+        let (time, increment) = match self.chess.turn {
+            Color::White => ("wtime", "winc"),
+            Color::Black => ("btime", "binc"),
+        };
+        let budget = if let Some(depth) = number("depth") {
+            Some(Budget::Depth(depth.min(u8::MAX as u64) as u8))
+        } else if let Some(millis) = number("movetime") {
+            Some(Budget::Time(Duration::from_millis(millis)))
+        } else if let Some(nodes) = number("nodes") {
+            Some(Budget::Nodes(nodes))
+        } else if let Some(left) = number(time) {
+            let increment = number(increment).unwrap_or(0);
+            let moves_to_go = number("movestogo").map(|moves| moves.min(u32::MAX as u64) as u32);
+            Some(time_budget(
+                Duration::from_millis(left),
+                Duration::from_millis(increment),
+                moves_to_go,
+            ))
+        } else {
+            None
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let limit = budget
+            .map_or_else(Limit::unlimited, Limit::new)
+            .with_stop(stop.clone());
+        let mut chess = self.chess.clone();
+        let table = self.table.clone();
+        let output = self.output.clone();
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let best_move = search::<Positional>(&mut chess, &mut table.lock().unwrap(), limit);
+            let mut output = output.lock().unwrap();
+            let mut info = format!(
+                "info depth {} score {} time {}",
+                best_move.depth,
+                uci_score(best_move.score, chess.turn),
+                start.elapsed().as_millis()
+            );
+            if let Some(m) = best_move.m {
+                info += &format!(" pv {m}");
+            }
+            write_line(&mut *output, &info);
+            match best_move.m {
+                Some(m) => write_line(&mut *output, &format!("bestmove {m}")),
+                // The game is over, so there is no move to play.
+                None => write_line(&mut *output, "bestmove 0000"),
+            }
+        });
+        self.search = Some(Search { thread, stop });
+    }
+
+    /// Handles a `setoption` command, for example `setoption name Hash value 32`.
+    fn set_option(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let value_at = arguments
+            .iter()
+            .position(|word| *word == "value")
+            .unwrap_or(arguments.len());
+        let name = match &arguments[..value_at] {
+            ["name", name @ ..] => name.join(" "),
+            _ => return Err("expected a name in the setoption command".to_owned()),
+        };
+        let value = arguments.get(value_at + 1..).unwrap_or_default().join(" ");
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => {
+                let megabytes = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|megabytes| (1..=MAX_HASH).contains(megabytes))
+                    .ok_or_else(|| format!("the Hash has to be from 1 to {MAX_HASH}"))?;
+                *self.table.lock().unwrap() = table_of(megabytes);
+                Ok(())
+            }
+            "clear hash" => {
+                self.table.lock().unwrap().clear();
+                Ok(())
+            }
+            "uci_chess960" => {
+                self.chess960 = value
+                    .parse()
+                    .map_err(|_| "UCI_Chess960 has to be true or false".to_owned())?;
+                Ok(())
+            }
+            _ => Err(format!("unknown option {name}")),
+        }
+    }
+
+    /// Stops the running search, which then sends the best move it found so far.
+    fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// Waits until the running search has sent its best move.
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.thread.join().unwrap();
+        }
+    }
+
+    fn send(&self, line: &str) {
+        write_line(&mut *self.output.lock().unwrap(), line);
+    }
+}
+
+fn write_line(output: &mut impl Write, line: &str) {
+    writeln!(output, "{line}").unwrap();
+    output.flush().unwrap();
+}
+
+/// Creates a [`TranspositionTable`] that takes up about the given number of megabytes.
+fn table_of(megabytes: usize) -> TranspositionTable {
+    TranspositionTable::new(megabytes * 1024 * 1024 / mem::size_of::<Option<Entry>>())
+}
+
+/// Writes a score as UCI expects it: from the side of the player to move, as `mate` followed by
+/// the number of moves until the mate, or as `cp` followed by the evaluation in centipawns.
+fn uci_score(score: Score, turn: Color) -> String {
+    let score = match turn {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    match (score.mate_in(), score.value()) {
+        (Some(moves), _) => format!("mate {moves}"),
+        (None, value) => format!("cp {}", value.unwrap_or_default()),
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::uci::*;
+
+    /// Handles the commands one by one, waits for the search to finish, and returns the output.
+    fn answers(commands: &[&str]) -> Vec<String> {
+        let mut uci = Uci::new(Vec::new());
+        for command in commands {
+            assert!(uci.handle(command));
+        }
+        uci.wait();
+        let output = uci.output.lock().unwrap();
+        String::from_utf8(output.clone())
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn test_handshake() {
+        let output = answers(&["uci", "isready", "unknown command"]);
+        assert!(output[0].starts_with("id name"));
+        assert_eq!(output[output.len() - 2..], ["uciok", "readyok"]);
+    }
+
+    #[test]
+    fn test_position() {
+        let mut uci = Uci::new(Vec::new());
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+        assert_eq!(uci.chess.to_fen(), fen);
+        // Moves are played from a FEN as well, and the moves are kept to detect repetitions.
+        uci.handle(&format!("position fen {fen} moves b8c6 f3g1 c6b8 g1f3"));
+        assert_eq!(uci.chess.to_fen(), fen.replace("1 2", "5 4"));
+        assert_eq!(uci.chess.history.len(), 4);
+
+        // An illegal move is reported and leaves the position as it was.
+        uci.handle("position startpos moves e2e5");
+        assert_eq!(uci.chess.history.len(), 4);
+        let output = uci.output.lock().unwrap();
+        assert!(String::from_utf8_lossy(&output).starts_with("info string could not play e2e5"));
+    }
+
+    #[test]
+    fn test_chess960() {
+        let mut uci = Uci::new(Vec::new());
+        uci.handle("setoption name UCI_Chess960 value true");
+        // The king castles by moving onto the rook, in classical chess as well.
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        uci.handle(&format!("position fen {fen} moves e1h1"));
+        assert_eq!(
+            uci.chess.to_fen(),
+            "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R4RK1 b kq - 1 1"
+        );
+
+        uci.handle("position fen nrbqkbrn/pppppppp/8/8/8/8/PPPPPPPP/NRBQKBRN w GBgb - 0 1");
+        assert!(uci.chess.chess960);
+        assert_eq!(uci.chess.to_fen().split(' ').nth(2), Some("KQkq"));
+    }
+
+    #[test]
+    fn test_go() {
+        // White mates with Rb8.
+        let output = answers(&["position fen 7k/R7/8/8/8/8/1R6/7K w - - 0 1", "go depth 3"]);
+        assert!(output[0].starts_with("info depth 1 score mate 1"));
+        assert_eq!(output[1], "bestmove b2b8");
+        // The score is given from the side of black when black is to move.
+        let output = answers(&[
+            "position fen 7k/1r6/8/8/8/8/r7/7K b - - 0 1",
+            "go movetime 100",
+        ]);
+        assert!(output[0].starts_with("info depth 1 score mate 1"));
+        assert_eq!(output[1], "bestmove b7b1");
+
+        let output = answers(&["position startpos", "go depth 2"]);
+        assert!(output[0].starts_with("info depth 2 score cp"));
+        assert!(output[1].starts_with("bestmove"));
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut uci = Uci::new(Vec::new());
+        uci.handle("position startpos");
+        uci.handle("go infinite");
+        uci.handle("isready");
+        uci.handle("stop");
+        let output = uci.output.lock().unwrap();
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.starts_with("readyok"));
+        assert!(output.lines().last().unwrap().starts_with("bestmove"));
+    }
+
+    #[test]
+    fn test_set_option() {
+        let output = answers(&[
+            "setoption name Hash value 1",
+            "setoption name Clear Hash",
+            "setoption name Hash value 0",
+            "setoption name Ponder value true",
+        ]);
+        assert_eq!(
+            output,
+            [
+                format!("info string the Hash has to be from 1 to {MAX_HASH}"),
+                "info string unknown option Ponder".to_owned(),
+            ]
+        );
+    }
+}
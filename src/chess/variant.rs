@@ -0,0 +1,336 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::chess::{
+    chess::{Chess, Color, Kind, Move, Outcome},
+    pos::Pos,
+};
+
+/// The rules of a variant of chess, which may differ from standard chess in how the game is won,
+/// which moves are allowed and what a good position looks like. Every method follows the rules of
+/// standard chess unless a variant overrides it.
+///
+/// The variant of a game is stored in [`Chess::rules`], which [`Chess::moves`],
+/// [`Chess::outcome`] and the search of the chess computer ask for the rules.
+pub trait Variant: Sync {
+    /// Whether the king may not be left in check. Without a royal king, any move of the pieces
+    /// is allowed and the king can be captured like any other piece.
+    fn royal_king(&self) -> bool {
+        true
+    }
+
+    /// Whether [`Chess::checks`] counts the checks given by each player.
+    fn counts_checks(&self) -> bool {
+        false
+    }
+
+    /// Changes the moves the player to move is allowed to make, for example to force captures.
+    /// The moves are those of standard chess, without the ones that leave a royal king in check.
+    fn filter_moves(&self, _chess: &Chess, _moves: &mut Vec<Move>) {}
+
+    /// Returns the outcome of a game that the variant ends before the rules of standard chess
+    /// are looked at, for example because a king reached the centre.
+    fn outcome(&self, _chess: &Chess) -> Option<Outcome> {
+        None
+    }
+
+    /// Returns the outcome of a game in which the player to move has no moves left: a checkmate
+    /// or a stalemate.
+    fn no_moves(&self, chess: &Chess) -> Outcome {
+        if chess.is_checked(chess.turn) {
+            Outcome::Winner(!chess.turn)
+        } else {
+            Outcome::Stalemate
+        }
+    }
+
+    /// Whether neither player has enough pieces left to win, which draws the game.
+    fn is_insufficient_material(&self, chess: &Chess) -> bool {
+        chess.is_insufficient_material()
+    }
+
+    /// Changes the score an [`Evaluator`] gives a position, to what is worth striving for in the
+    /// variant. A positive score means that white is better off.
+    ///
+    /// [`Evaluator`]: crate::chess::evaluation::Evaluator
+    fn evaluate(&self, _chess: &Chess, evaluation: i16) -> i16 {
+        evaluation
+    }
+}
+
+/// The variants of chess that can be played, see [`Variant`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Rules {
+    #[default]
+    Standard,
+    KingOfTheHill,
+    ThreeCheck,
+    Antichess,
+}
+
+impl Rules {
+    /// Returns the rules of the variant.
+    pub fn variant(&self) -> &'static dyn Variant {
+        match self {
+            Rules::Standard => &Standard,
+            Rules::KingOfTheHill => &KingOfTheHill,
+            Rules::ThreeCheck => &ThreeCheck,
+            Rules::Antichess => &Antichess,
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    /// Reads a variant from its name, like `three-check`. Spaces, dashes and case do not matter.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect();
+        match name.to_ascii_lowercase().as_str() {
+            "standard" | "chess" => Ok(Rules::Standard),
+            "kingofthehill" | "koth" => Ok(Rules::KingOfTheHill),
+            "threecheck" | "3check" => Ok(Rules::ThreeCheck),
+            "antichess" => Ok(Rules::Antichess),
+            _ => Err(format!(
+                "unknown variant \"{s}\", use standard, king-of-the-hill, three-check or antichess"
+            )),
+        }
+    }
+}
+
+/// Writes the name of the variant as it is used in the Variant tag of a PGN.
+impl Display for Rules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rules::Standard => write!(f, "Standard"),
+            Rules::KingOfTheHill => write!(f, "King of the Hill"),
+            Rules::ThreeCheck => write!(f, "Three-check"),
+            Rules::Antichess => write!(f, "Antichess"),
+        }
+    }
+}
+
+/// The rules of standard chess.
+pub struct Standard;
+
+impl Variant for Standard {}
+
+/// Besides by checkmate, a player wins by bringing their king to one of the four squares in the
+/// centre of the board, the hill.
+pub struct KingOfTheHill;
+
+/// How much a king is worth for every step it is closer to the hill, counted from four steps
+/// away.
+const HILL_BONUS: i16 = 30;
+
+impl KingOfTheHill {
+    /// The number of king steps from the given position to the nearest square of the hill.
+    fn distance(pos: Pos) -> i16 {
+        let steps = |z: isize| (3 - z).max(z - 4).max(0) as i16;
+        steps(pos.x()).max(steps(pos.y()))
+    }
+}
+
+impl Variant for KingOfTheHill {
+    fn outcome(&self, chess: &Chess) -> Option<Outcome> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|color| Self::distance(chess.kings[color.king_index()]) == 0)
+            .map(Outcome::Winner)
+    }
+
+    /// A lone king can still walk to the hill, so no position is drawn for lack of material.
+    fn is_insufficient_material(&self, _chess: &Chess) -> bool {
+        false
+    }
+
+    fn evaluate(&self, chess: &Chess, evaluation: i16) -> i16 {
+        let bonus = |color: Color| {
+            HILL_BONUS * (4 - Self::distance(chess.kings[color.king_index()])).max(0)
+        };
+        evaluation + bonus(Color::White) - bonus(Color::Black)
+    }
+}
+
+/// Besides by checkmate, a player wins by checking the king of the opponent three times.
+pub struct ThreeCheck;
+
+/// How much the checks a player has given are worth, by their number.
+const CHECK_BONUS: [i16; 3] = [0, 150, 400];
+
+impl Variant for ThreeCheck {
+    fn counts_checks(&self) -> bool {
+        true
+    }
+
+    fn outcome(&self, chess: &Chess) -> Option<Outcome> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|color| chess.checks[color.king_index()] >= 3)
+            .map(Outcome::Winner)
+    }
+
+    /// Checking the king needs more than a king, so only a game with just the kings is drawn.
+    fn is_insufficient_material(&self, chess: &Chess) -> bool {
+        Chess::board_positions()
+            .filter_map(|pos| chess[pos])
+            .all(|piece| piece.kind == Kind::King)
+    }
+
+    fn evaluate(&self, chess: &Chess, evaluation: i16) -> i16 {
+        let bonus = |color: Color| CHECK_BONUS[chess.checks[color.king_index()].min(2) as usize];
+        evaluation + bonus(Color::White) - bonus(Color::Black)
+    }
+}
+
+/// The player that loses all their pieces, or can not move anymore, wins. A player that can
+/// capture a piece has to, the king is no more than a piece that may be captured, there is no
+/// castling, and pawns may also be promoted to a king.
+pub struct Antichess;
+
+impl Variant for Antichess {
+    fn royal_king(&self) -> bool {
+        false
+    }
+
+    fn filter_moves(&self, chess: &Chess, moves: &mut Vec<Move>) {
+        let kings: Vec<Move> = moves
+            .iter()
+            .filter(|m| m.promotion == Some(Kind::Queen))
+            .map(|m| Move {
+                promotion: Some(Kind::King),
+                ..*m
+            })
+            .collect();
+        moves.extend(kings);
+        if moves.iter().any(|m| chess.captured_pos(*m).is_some()) {
+            moves.retain(|m| chess.captured_pos(*m).is_some());
+        }
+    }
+
+    fn no_moves(&self, chess: &Chess) -> Outcome {
+        Outcome::Winner(chess.turn)
+    }
+
+    /// A player can always be forced to capture, so no position is drawn for lack of material.
+    fn is_insufficient_material(&self, _chess: &Chess) -> bool {
+        false
+    }
+
+    /// Having fewer pieces is better, so the evaluation is turned around.
+    fn evaluate(&self, _chess: &Chess, evaluation: i16) -> i16 {
+        -evaluation
+    }
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Color, Kind, Move, Outcome},
+        computer::{search, Budget, Limit},
+        evaluation::Material,
+        pos::Pos,
+        transposition::TranspositionTable,
+        variant::*,
+    };
+
+    fn with_rules(fen: &str, rules: Rules) -> Chess {
+        let mut chess = Chess::from_fen(fen).unwrap();
+        chess.set_rules(rules);
+        chess
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!("King of the Hill".parse(), Ok(Rules::KingOfTheHill));
+        assert_eq!("3check".parse(), Ok(Rules::ThreeCheck));
+        assert_eq!("three-check".parse(), Ok(Rules::ThreeCheck));
+        assert!("crazyhouse".parse::<Rules>().is_err());
+        for rules in [
+            Rules::Standard,
+            Rules::KingOfTheHill,
+            Rules::ThreeCheck,
+            Rules::Antichess,
+        ] {
+            assert_eq!(rules.to_string().parse(), Ok(rules));
+        }
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let mut chess = with_rules("4k3/8/8/8/8/2K5/8/8 w - - 0 1", Rules::KingOfTheHill);
+        // Only the kings are left, which is not a draw in King of the Hill.
+        assert_eq!(chess.outcome(), None);
+        chess.perform(Move::new(Pos::new(2, 2), Pos::new(3, 3)));
+        assert_eq!(chess.outcome(), Some(Outcome::Winner(Color::White)));
+
+        // The computer walks its king onto the hill, past the rook that guards the other squares.
+        let mut chess = with_rules("4k3/8/8/5r2/8/2K5/8/8 w - - 0 1", Rules::KingOfTheHill);
+        let best = search::<Material>(
+            &mut chess,
+            &mut TranspositionTable::default(),
+            Limit::new(Budget::Depth(2)),
+        );
+        assert_eq!(best.m, Some(Move::new(Pos::new(2, 2), Pos::new(3, 3))));
+    }
+
+    #[test]
+    fn test_three_check() {
+        let mut chess = with_rules("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Rules::ThreeCheck);
+        let key = chess.position_key();
+        let checks = ["a1a8", "e8d7", "a8a7", "d7c6", "a7a6"];
+        for (number, uci) in checks.iter().enumerate() {
+            assert_eq!(chess.outcome(), None);
+            let m = chess.parse_uci(uci).unwrap();
+            chess.perform(m);
+            assert_eq!(chess.checks[0] as usize, (number + 2) / 2);
+        }
+        assert_eq!(chess.outcome(), Some(Outcome::Winner(Color::White)));
+
+        chess.undo();
+        assert_eq!(chess.checks, [2, 0]);
+        while chess.undo().is_some() {}
+        assert_eq!(chess.position_key(), key);
+    }
+
+    #[test]
+    fn test_antichess() {
+        let mut chess = Chess::default();
+        chess.set_rules(Rules::Antichess);
+        for uci in ["e2e4", "d7d5"] {
+            let m = chess.parse_uci(uci).unwrap();
+            chess.perform(m);
+        }
+        // Capturing the pawn is forced.
+        let moves: Vec<String> = chess.moves().map(|m| m.to_string()).collect();
+        assert_eq!(moves, ["e4d5"]);
+
+        // The king may be captured, and the player without pieces wins.
+        let mut chess = with_rules("8/8/8/8/8/8/3k4/4K3 w - - 0 1", Rules::Antichess);
+        let moves: Vec<Move> = chess.moves().collect();
+        assert_eq!(moves, [Move::new(Pos::new(4, 0), Pos::new(3, 1))]);
+        chess.perform(moves[0]);
+        assert_eq!(chess.outcome(), Some(Outcome::Winner(Color::Black)));
+
+        // A pawn may be promoted to a king.
+        let chess = with_rules("8/P7/8/8/8/8/8/k6K w - - 0 1", Rules::Antichess);
+        assert!(chess.moves().any(|m| m.promotion == Some(Kind::King)));
+
+        // There is no castling, not even after the king has been captured and another piece
+        // stands on its square.
+        let mut chess = with_rules("k7/8/8/8/1b6/3N4/8/4K2R b K - 0 1", Rules::Antichess);
+        assert_eq!(chess.to_fen(), "k7/8/8/8/1b6/3N4/8/4K2R b - - 0 1");
+        for uci in ["b4e1", "d3e1", "a8b8"] {
+            let m = chess.parse_uci(uci).unwrap();
+            chess.perform(m);
+        }
+        let moves: Vec<String> = chess.moves().map(|m| m.to_string()).collect();
+        assert!(!moves.contains(&"e1g1".to_owned()));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::chess::{
+    bitboard,
+    chess::{CastlingRights, Color, Piece},
+    pos::Pos,
+};
+
+/// Random keys for all features of a position. The first 768 keys are for a piece of each color
+/// and kind on each square, followed by 4 keys for the castling rights, 8 for the file of the en
+/// passant square, 1 for black to move, and 6 for the number of checks each player has given.
+const KEYS: [u64; 787] = random_keys();
+
+const CASTLING: usize = 768;
+const EN_PASSANT: usize = CASTLING + 4;
+const BLACK_TO_MOVE: usize = EN_PASSANT + 8;
+const CHECKS: usize = BLACK_TO_MOVE + 1;
+
+/// Returns the key of a piece on a square on the board. The key of a position is the exclusive
+/// or of the keys of all its features, so a piece is added to or removed from the key of a
+/// position by combining the key of the position with the key of the piece.
+pub fn piece(piece: Piece, pos: Pos) -> u64 {
+    KEYS[piece.color.king_index() * 384 + piece.kind.index() * 64 + bitboard::square(pos)]
+}
+
+/// Returns the key of everything but the pieces: the player to move, the castling rights and the
+/// en passant square.
+pub fn state(turn: Color, castling_rights: &[CastlingRights; 2], en_passant: Option<Pos>) -> u64 {
+    let mut key = 0;
+    for (index, rights) in castling_rights.iter().enumerate() {
+        if rights.king_side {
+            key ^= KEYS[CASTLING + 2 * index];
+        }
+        if rights.queen_side {
+            key ^= KEYS[CASTLING + 2 * index + 1];
+        }
+    }
+    if let Some(pos) = en_passant {
+        key ^= KEYS[EN_PASSANT + pos.x() as usize];
+    }
+    if turn == Color::Black {
+        key ^= KEYS[BLACK_TO_MOVE];
+    }
+    key
+}
+
+/// Returns the key of the number of checks each player has given, which is 0 when no checks
+/// have been given. From the third check on the number does not matter, as that check wins.
+pub fn checks(checks: &[u8; 2]) -> u64 {
+    let mut key = 0;
+    for (index, count) in checks.iter().enumerate() {
+        if *count > 0 {
+            key ^= KEYS[CHECKS + 3 * index + (*count).min(3) as usize - 1];
+        }
+    }
+    key
+}
+
+/// Generates the keys with the SplitMix64 generator from a fixed seed, so the keys are the same
+/// in every run.
+const fn random_keys() -> [u64; 787] {
+    let mut keys = [0; 787];
+    let mut state: u64 = 0x0123_4567_89AB_CDEF;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+//TESTS
+#[cfg(test)]
+mod tests {
+    use crate::chess::{
+        chess::{Chess, Move},
+        pos::Pos,
+    };
+
+    /// Plays a list of moves in UCI long notation from the start position.
+    fn play(moves: &[&str]) -> Chess {
+        let mut chess = Chess::default();
+        for uci in moves {
+            let m = chess.parse_uci(uci).unwrap();
+            chess.perform(m);
+        }
+        chess
+    }
+
+    #[test]
+    fn test_position_key() {
+        // 1. Nf3 Nf6 2. Nc3 and 1. Nc3 Nf6 2. Nf3 reach the same position.
+        let first = play(&["g1f3", "g8f6", "b1c3"]);
+        let second = play(&["b1c3", "g8f6", "g1f3"]);
+        assert_eq!(first.position_key(), second.position_key());
+        // The key that was updated move by move is the same as the key of the position read
+        // from a FEN.
+        let read = Chess::from_fen(&first.to_fen()).unwrap();
+        assert_eq!(first.position_key(), read.position_key());
+
+        // The same pieces with the other player to move is another position.
+        let mut other = read.clone();
+        other.turn = !other.turn;
+        assert_ne!(read.position_key(), other.position_key());
+
+        // Taking back a move restores the key.
+        let mut chess = first.clone();
+        chess.perform(Move::new(Pos::new(4, 6), Pos::new(4, 4)));
+        assert_ne!(chess.position_key(), first.position_key());
+        chess.undo();
+        assert_eq!(chess.position_key(), first.position_key());
+    }
+}
@@ -1,14 +1,20 @@
+use std::collections::VecDeque;
 use std::ops::Not;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::{
-    chess::{chess::Color, chess::Move, pos::Pos, BoardState},
+    chess::{
+        chess::Color, chess::Move, chess::Outcome, chess::Piece, clock::Clock, computer::Thinking, pos::Pos,
+        BoardState, GameHistory,
+    },
     pathfinding::astar::Path,
 };
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 /// Is true when the hardware magnet has reached it's position
 static POLLING_DONE: AtomicBool = AtomicBool::new(false);
+/// File the game is saved to as a PGN.
+pub const PGN_PATH: &str = "game.pgn";
 /// Plugin initilizing the resources and running the systems for the bevy app.
 pub struct ControllerPlugin;
 
@@ -19,6 +25,9 @@ impl Plugin for ControllerPlugin {
             .init_resource::<MagnetStatus>()
             .init_resource::<PlayerTurn>()
             .init_resource::<Setup>()
+            .init_resource::<Replay>()
+            .init_resource::<TakeBack>()
+            .init_resource::<DrawOffer>()
             .insert_resource(Destination {
                 goal: Pos { x: 0, y: 0 },
             })
@@ -26,6 +35,7 @@ impl Plugin for ControllerPlugin {
                 current_move: Move {
                     from: Pos { x: 0, y: 0 },
                     to: Pos { x: 0, y: 0 },
+                    promotion: None,
                 },
             })
             .add_event::<MoveEvent>()
@@ -35,12 +45,22 @@ impl Plugin for ControllerPlugin {
             .add_event::<MagnetEvent>()
             .add_event::<EndTurnEvent>()
             .add_event::<ComputerTurnEvent>()
-            .add_system(update_path)
+            .add_event::<UndoEvent>()
+            .add_event::<StartTurnEvent>()
+            .add_event::<GameOverEvent>()
+            .add_system(update_path.after(game_over))
             .add_system(update_locations)
             .add_system(update_current_pos)
             .add_system(set_first_pos)
             .add_system(poll_system)
-            .add_system(end_turn);
+            .add_system(end_turn)
+            .add_system(end_take_back.after(end_turn))
+            .add_system(start_game.after(end_turn))
+            .add_system(start_turn.after(end_turn).before(replay_move))
+            .add_system(replay_move.after(end_turn))
+            .add_system(pause_clock.before(end_turn))
+            .add_system(run_clock.before(game_over))
+            .add_system(game_over);
     }
 }
 
@@ -76,6 +96,38 @@ pub struct Setup {
     pub complete: bool,
     pub turn_ended: bool,
 }
+///The moves of a recorded game that are performed on the board one after the other,
+/// instead of moves by the players.
+#[derive(Resource, Default, Debug)]
+pub struct Replay {
+    pub active: bool,
+    pub moves: VecDeque<Move>,
+}
+
+///The number of moves that are still to be taken back. While this is not zero, the paths the
+/// magnet covers take back the last move instead of making a new one.
+#[derive(Resource, Default, Debug)]
+pub struct TakeBack {
+    pub moves: usize,
+}
+
+///A draw offered by the computer player. The human player can accept it until they make their
+/// next move.
+#[derive(Resource, Default, Debug)]
+pub struct DrawOffer {
+    /// The number of moves that had been played when the draw was last offered.
+    pub moves: Option<usize>,
+}
+
+impl DrawOffer {
+    /// Whether the draw can still be accepted when the given number of moves has been played.
+    /// The offer is made together with the move of the computer, and stands until the next move
+    /// is made or taken back.
+    pub fn is_open(&self, moves: usize) -> bool {
+        moves > 0 && self.moves == Some(moves - 1)
+    }
+}
+
 ///Vector with all the paths the magnet still has to cover.
 #[derive(Resource, Default, Debug)]
 pub struct CurrentPaths {
@@ -100,6 +152,13 @@ pub struct CurrentMove {
     pub current_move: Move,
 }
 
+/// The move a player makes, and the event that starts making it on the board.
+#[derive(SystemParam)]
+pub struct NewMove<'w> {
+    pub current_move: ResMut<'w, CurrentMove>,
+    pub event: EventWriter<'w, MoveEvent>,
+}
+
 /// This struct keeps track of whether the magnet is currently moving,
 /// whether the magnet hsa reached its destination (simulation and real),
 /// and whether the magnet is currently on.
@@ -128,6 +187,19 @@ pub struct FirstMoveEvent;
 pub struct MagnetEvent;
 pub struct EndTurnEvent;
 pub struct ComputerTurnEvent;
+pub struct UndoEvent;
+
+/// Sent when the player to move can make their move: at the start of the game, after a move, and
+/// after moves have been taken back, or could not be taken back.
+pub struct StartTurnEvent {
+    /// Whether the other player just made a move, for which they get their increment.
+    pub moved: bool,
+}
+
+/// Sent when the game is over, with the outcome that tells who won and why.
+pub struct GameOverEvent {
+    pub outcome: Outcome,
+}
 
 /// System that polls to the hardware implementation whether the magnet has yet reached its destination.
 /// It only polls when the magnet is moving.
@@ -157,15 +229,20 @@ fn poll() {
 }
 
 /// When a new [`MoveEvent`] is registered this function sends a PathEvent which triggers the function
-/// [`give_path`] in astar.rs.
+/// [`give_path`] in astar.rs. Once the game is over, moves are refused.
 ///
 /// [`give_path`]: crate::pathfinding::astar::give_path
 pub(crate) fn update_path(
     mut new_move: EventReader<MoveEvent>,
     mut new_path: EventWriter<PathEvent>,
+    history: Res<GameHistory>,
 ) {
     for _event in new_move.iter() {
-        new_path.send(PathEvent);
+        if history.game.outcome.is_some() {
+            println!("The game is over, no more moves can be made.");
+        } else {
+            new_path.send(PathEvent);
+        }
     }
 }
 
@@ -274,33 +351,229 @@ fn update_pos(
     }
 }
 
-/// When a new [`EndTurnEvent`] is registered this function checks whether this function has been called before.
-/// When this is not the case, it sets the value in [`Setup`] to true. If this function is called when setp is true,
-/// this means the move has been executed. The function updates all the resources linked to the current turn.
-/// After this, the system is ready for a new move from either computer or human player.
+/// The state of the game and of the magnet that changes when a move has been made on the board,
+/// or has been taken back.
+#[derive(SystemParam)]
+struct TurnState<'w> {
+    current_locations: ResMut<'w, CurrentPaths>,
+    magnet_status: ResMut<'w, MagnetStatus>,
+    player_turn: ResMut<'w, PlayerTurn>,
+    boardstate: ResMut<'w, BoardState>,
+    history: ResMut<'w, GameHistory>,
+}
+
+impl TurnState<'_> {
+    /// Turns off the magnet now all paths have been covered, and passes the turn to the other
+    /// player.
+    fn pass_turn(&mut self) {
+        *self.current_locations = CurrentPaths { paths: vec![] };
+        self.magnet_status.on = false;
+        self.magnet_status.moving = false;
+        self.player_turn.turn = !self.player_turn.turn;
+    }
+}
+
+/// When a new [`EndTurnEvent`] is registered after the setup, the move has been executed. The
+/// function updates all the resources linked to the current turn. After this, the system is ready
+/// for a new move from either computer or human player.
+/// Every performed move is recorded in [`GameHistory`]. When the game is over, a [`GameOverEvent`]
+/// is sent, otherwise a [`StartTurnEvent`] for the next move.
+/// When a pawn is promoted to a kind of piece that has not been captured yet, there is no piece to
+/// swap it for, and the player is told the pawn stays on the board in its place.
+/// The first [`EndTurnEvent`] is left to [`start_game`], and the ones while moves are taken back
+/// to [`end_take_back`], which both run after this function.
 fn end_turn(
     mut end_turn: EventReader<EndTurnEvent>,
-    mut computer_turn: EventWriter<ComputerTurnEvent>,
-    mut current_locations: ResMut<CurrentPaths>,
-    mut magnet_status: ResMut<MagnetStatus>,
-    mut player_turn: ResMut<PlayerTurn>,
-    mut boardstate: ResMut<BoardState>,
+    mut state: TurnState,
     current_move: Res<CurrentMove>,
-    mut setup: ResMut<Setup>,
+    setup: Res<Setup>,
+    take_back: Res<TakeBack>,
+    mut start_turn: EventWriter<StartTurnEvent>,
+    mut game_over: EventWriter<GameOverEvent>,
 ) {
     for _event in end_turn.iter() {
-        if setup.complete {
-            *current_locations = CurrentPaths { paths: vec![] };
-            magnet_status.on = false;
-            magnet_status.moving = false;
-            let m = current_move.current_move;
-            boardstate.chess.perform(m);
-            player_turn.turn = !player_turn.turn;
-            if player_turn.turn == Player::Computer {
-                computer_turn.send(ComputerTurnEvent);
+        if !setup.complete || take_back.moves > 0 {
+            continue;
+        }
+        state.pass_turn();
+        let m = current_move.current_move;
+        let chess = &state.boardstate.chess;
+        if let (Some(kind), Some(pawn)) = (m.promotion, chess[m.from]) {
+            let promoted = Piece {
+                color: pawn.color,
+                kind,
+            };
+            if chess.graveyard_find(promoted, m.to).is_none() {
+                println!(
+                    "There is no captured {kind} to swap for the pawn, so the pawn stays on the board as the {kind}."
+                );
             }
+        }
+        state.history.game.push(&state.boardstate.chess, m);
+        state.boardstate.chess.perform(m);
+        if let Some(outcome) = state.boardstate.chess.outcome() {
+            game_over.send(GameOverEvent { outcome });
         } else {
+            start_turn.send(StartTurnEvent { moved: true });
+        }
+    }
+}
+
+/// When a new [`EndTurnEvent`] is registered while moves are taken back as counted in
+/// [`TakeBack`], the last move has been taken back on the board, and is undone. An [`UndoEvent`]
+/// is sent for the next move to take back, or a [`StartTurnEvent`] after the last one.
+fn end_take_back(
+    mut end_turn: EventReader<EndTurnEvent>,
+    mut state: TurnState,
+    mut take_back: ResMut<TakeBack>,
+    mut undo: EventWriter<UndoEvent>,
+    mut start_turn: EventWriter<StartTurnEvent>,
+) {
+    for _event in end_turn.iter() {
+        if take_back.moves == 0 {
+            continue;
+        }
+        state.pass_turn();
+        state.boardstate.chess.undo();
+        state.history.game.undo();
+        take_back.moves -= 1;
+        if take_back.moves > 0 {
+            undo.send(UndoEvent);
+        } else {
+            start_turn.send(StartTurnEvent { moved: false });
+        }
+    }
+}
+
+/// When the first [`EndTurnEvent`] is registered, the pieces have been set up and the value in
+/// [`Setup`] is set to true. A [`StartTurnEvent`] is sent for the first move, which may be the
+/// computer's when the game starts from a position where it has to move first.
+fn start_game(
+    mut end_turn: EventReader<EndTurnEvent>,
+    mut setup: ResMut<Setup>,
+    mut start_turn: EventWriter<StartTurnEvent>,
+) {
+    for _event in end_turn.iter() {
+        if !setup.complete {
             setup.complete = true;
+            start_turn.send(StartTurnEvent { moved: false });
         }
     }
 }
+
+/// When a new [`StartTurnEvent`] is registered, the player who moved gets their increment on the
+/// [`Clock`], when the game is played with one, and the clock of the player to move is started.
+/// The computer is asked for a move when it is its turn. While a game is being replayed nothing
+/// happens, as [`replay_move`] makes the moves.
+fn start_turn(
+    mut start_turn: EventReader<StartTurnEvent>,
+    replay: Res<Replay>,
+    boardstate: Res<BoardState>,
+    player_turn: Res<PlayerTurn>,
+    mut computer_turn: EventWriter<ComputerTurnEvent>,
+    mut clock: Option<ResMut<Clock>>,
+) {
+    for event in start_turn.iter() {
+        if replay.active {
+            continue;
+        }
+        if let Some(clock) = clock.as_mut() {
+            if event.moved {
+                clock.finish_move();
+            }
+            clock.start(boardstate.chess.turn);
+        }
+        if player_turn.turn == Player::Computer {
+            computer_turn.send(ComputerTurnEvent);
+        }
+    }
+}
+
+/// When a new [`EndTurnEvent`] is registered while a game is being replayed, this function puts the
+/// next move of the [`Replay`] in [`CurrentMove`] and sends a [`MoveEvent`], just like a player would.
+/// When all moves have been replayed, the players take over from the final position with a
+/// [`StartTurnEvent`].
+fn replay_move(
+    mut end_turn: EventReader<EndTurnEvent>,
+    mut replay: ResMut<Replay>,
+    mut current_move: ResMut<CurrentMove>,
+    mut new_move: EventWriter<MoveEvent>,
+    mut start_turn: EventWriter<StartTurnEvent>,
+) {
+    for _event in end_turn.iter() {
+        if replay.active {
+            match replay.moves.pop_front() {
+                Some(m) => {
+                    current_move.current_move = m;
+                    new_move.send(MoveEvent);
+                }
+                None => {
+                    replay.active = false;
+                    println!("The replay has finished.");
+                    start_turn.send(StartTurnEvent { moved: false });
+                }
+            }
+        }
+    }
+}
+
+/// Pauses the [`Clock`] when a [`MoveEvent`] or [`UndoEvent`] is registered, so the time the
+/// magnet takes to move the pieces is not counted. It starts again in [`start_turn`].
+fn pause_clock(
+    mut new_move: EventReader<MoveEvent>,
+    mut undo: EventReader<UndoEvent>,
+    clock: Option<ResMut<Clock>>,
+) {
+    let moving = new_move.iter().count() + undo.iter().count() > 0;
+    if let Some(mut clock) = clock.filter(|_| moving) {
+        clock.pause();
+    }
+}
+
+/// Lets the time of every frame pass on the [`Clock`], when the game is played with one. When
+/// the time of a player runs out, they lose the game and a [`GameOverEvent`] is sent.
+fn run_clock(
+    time: Res<Time>,
+    clock: Option<ResMut<Clock>>,
+    mut game_over: EventWriter<GameOverEvent>,
+) {
+    let Some(mut clock) = clock else {
+        return;
+    };
+    if let Some(color) = clock.tick(time.delta()) {
+        game_over.send(GameOverEvent {
+            outcome: Outcome::OutOfTime(color),
+        });
+    }
+}
+
+/// When a new [`GameOverEvent`] is registered while the game is still going on, its outcome is
+/// recorded in [`GameHistory`] and printed, and the game is saved as a PGN. The computer stops
+/// thinking and the [`Clock`] stops, and no more moves are made, see [`update_path`].
+fn game_over(
+    mut game_over: EventReader<GameOverEvent>,
+    mut history: ResMut<GameHistory>,
+    mut thinking: ResMut<Thinking>,
+    clock: Option<ResMut<Clock>>,
+) {
+    for event in game_over.iter() {
+        if history.game.outcome.is_some() {
+            continue;
+        }
+        thinking.cancel();
+        println!("{}!", event.outcome);
+        history.game.outcome = Some(event.outcome);
+        save_game(&history);
+    }
+    if let Some(mut clock) = clock.filter(|_| history.game.outcome.is_some()) {
+        clock.pause();
+    }
+}
+
+/// Saves the game in [`GameHistory`] as a PGN in [`PGN_PATH`].
+pub fn save_game(history: &GameHistory) {
+    match history.game.save(PGN_PATH) {
+        Ok(()) => println!("The game has been saved to {PGN_PATH}."),
+        Err(error) => println!("Could not save the game to {PGN_PATH}: {error}"),
+    }
+}
@@ -1,4 +1,5 @@
 /// Handles the communication between the different components
+#[allow(clippy::module_inception)]
 pub mod controller;
 /// Makes it possible for a human player to perform a move, using the visualization in Bevy.
 pub mod ui;
@@ -1,9 +1,19 @@
-use super::controller::{self, CurrentMove, MoveEvent, Player, PlayerTurn};
+use super::controller::{
+    self, save_game, CurrentPaths, DrawOffer, GameOverEvent, MagnetStatus, MoveEvent, NewMove,
+    Player, PlayerTurn, Replay, TakeBack, UndoEvent,
+};
 use crate::{
-    chess::{chess::Move, pos::Pos},
-    simulation::{board::Square, pieces::PieceComponent},
+    chess::{
+        chess::{Chess, Draw, Kind, Outcome},
+        clock::Clock,
+        computer::Thinking,
+        pos::Pos,
+        BoardState, GameHistory,
+    },
+    pathfinding::astar::give_undo_path,
+    simulation::{app::TITLE, board::Square, pieces::PieceComponent},
 };
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
 /// Plugin initilizing the resources and running the systems for the bevy app.
 pub struct UserInterfacePlugin;
 
@@ -11,7 +21,189 @@ impl Plugin for UserInterfacePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SelectedSquare>()
             .init_resource::<SelectedPiece>()
-            .add_system(perform_move);
+            .add_system(perform_move)
+            .add_system(save_on_key)
+            .add_system(undo_on_key)
+            .add_system(point_returning_pieces.after(give_undo_path))
+            .add_system(resign_on_key)
+            .add_system(draw_on_key)
+            .add_system(show_status);
+    }
+}
+
+/// Saves the game so far as a PGN when the S key is pressed.
+fn save_on_key(keys: Res<Input<KeyCode>>, history: Res<GameHistory>) {
+    if keys.just_pressed(KeyCode::S) {
+        save_game(&history);
+    }
+}
+
+/// Takes back the last move of both players when the U key is pressed, so that it is the human
+/// player's turn again. When the game ended with a move of the human player, only that move is
+/// taken back. While the computer is thinking about its answer, it stops and the last move of
+/// the human player is taken back. Nothing happens while a move is being made or a game is being
+/// replayed, or after a game that did not end on the board: by resignation, an agreed draw or
+/// a loss on time.
+fn undo_on_key(
+    keys: Res<Input<KeyCode>>,
+    player_turn: Res<PlayerTurn>,
+    mut thinking: ResMut<Thinking>,
+    mut state: TakeBackState,
+) {
+    if !keys.just_pressed(KeyCode::U)
+        || state.replay.active
+        || state.take_back.moves > 0
+        || state.magnet_status.moving
+        || !state.current_paths.paths.is_empty()
+        || matches!(
+            state.history.game.outcome,
+            Some(Outcome::OutOfTime(_) | Outcome::Resigned(_) | Outcome::Draw(Draw::Agreement))
+        )
+    {
+        return;
+    }
+    let moves = match player_turn.turn {
+        Player::Human => 2,
+        Player::Computer if state.history.game.outcome.is_some() || thinking.is_thinking() => 1,
+        Player::Computer => return,
+    };
+    if state.history.game.moves.len() < moves {
+        println!("There is no move to take back.");
+        return;
+    }
+    thinking.cancel();
+
+    state.take_back.moves = moves;
+    state.undo.send(UndoEvent);
+}
+
+/// The state of the game [`undo_on_key`] has to wait for before it takes back moves, and the
+/// resources it changes to do so.
+#[derive(SystemParam)]
+struct TakeBackState<'w> {
+    history: Res<'w, GameHistory>,
+    replay: Res<'w, Replay>,
+    magnet_status: Res<'w, MagnetStatus>,
+    current_paths: Res<'w, CurrentPaths>,
+    take_back: ResMut<'w, TakeBack>,
+    undo: EventWriter<'w, UndoEvent>,
+}
+
+/// When the paths that take back a move have been found, the pieces that return are pointed to
+/// the squares they return to, so they can be selected there again.
+fn point_returning_pieces(
+    mut undo: EventReader<UndoEvent>,
+    take_back: Res<TakeBack>,
+    boardstate: Res<BoardState>,
+    mut pieces_query: Query<&mut PieceComponent>,
+) {
+    if undo.iter().count() == 0 || take_back.moves == 0 {
+        return;
+    }
+    let mut during_undo = boardstate.chess.clone();
+    for m in boardstate.chess.undo_moves() {
+        if let Some(piece) = during_undo[m.from] {
+            if Chess::on_board(&m.from) && Chess::on_board(&m.to) {
+                if let Some(mut returning) = pieces_query.iter_mut().find(|returning| {
+                    returning.piece == piece
+                        && returning.target_x as isize == m.from.y()
+                        && returning.target_y as isize == m.from.x()
+                }) {
+                    returning.target_x = m.to.y() as usize;
+                    returning.target_y = m.to.x() as usize;
+                }
+            }
+        }
+        during_undo.move_piece(m.from, m.to);
+    }
+}
+
+/// The state of the game the human player has to wait for before they can resign or agree to a
+/// draw.
+#[derive(SystemParam)]
+struct GameState<'w> {
+    history: Res<'w, GameHistory>,
+    replay: Res<'w, Replay>,
+    take_back: Res<'w, TakeBack>,
+    magnet_status: Res<'w, MagnetStatus>,
+    current_paths: Res<'w, CurrentPaths>,
+}
+
+impl GameState<'_> {
+    /// Whether the game is over, or a move is being made, taken back or replayed.
+    fn is_busy(&self) -> bool {
+        self.history.game.outcome.is_some()
+            || self.replay.active
+            || self.take_back.moves > 0
+            || self.magnet_status.moving
+            || !self.current_paths.paths.is_empty()
+    }
+}
+
+/// The human player resigns when the R key is pressed, which ends the game with a
+/// [`GameOverEvent`].
+fn resign_on_key(
+    keys: Res<Input<KeyCode>>,
+    player_turn: Res<PlayerTurn>,
+    state: GameState,
+    mut game_over: EventWriter<GameOverEvent>,
+) {
+    if !keys.just_pressed(KeyCode::R) || state.is_busy() {
+        return;
+    }
+    game_over.send(GameOverEvent {
+        outcome: Outcome::Resigned(player_turn.color),
+    });
+}
+
+/// When the D key is pressed, the human player accepts the [`DrawOffer`] of the computer while it
+/// stands, or offers a draw to the computer otherwise. The computer accepts a draw when it is not
+/// better off, see [`Thinking::accepts_draw`]. An agreed draw ends the game with a
+/// [`GameOverEvent`].
+fn draw_on_key(
+    keys: Res<Input<KeyCode>>,
+    player_turn: Res<PlayerTurn>,
+    state: GameState,
+    draw_offer: Res<DrawOffer>,
+    thinking: Res<Thinking>,
+    mut game_over: EventWriter<GameOverEvent>,
+) {
+    if !keys.just_pressed(KeyCode::D) || state.is_busy() {
+        return;
+    }
+    if draw_offer.is_open(state.history.game.moves.len()) {
+        println!("The draw offered by the computer is accepted.");
+    } else if thinking.accepts_draw(!player_turn.color) {
+        println!("The computer accepts the draw.");
+    } else {
+        println!("The computer declines the draw.");
+        return;
+    }
+    game_over.send(GameOverEvent {
+        outcome: Outcome::Draw(Draw::Agreement),
+    });
+}
+
+/// Shows in the title of the window the time left on the [`Clock`], when the game is played with
+/// one, and when the computer is thinking about its move.
+fn show_status(
+    thinking: Res<Thinking>,
+    clock: Option<Res<Clock>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mut title = TITLE.to_owned();
+    if let Some(clock) = clock {
+        title.push_str(&format!(" - {}", *clock));
+    }
+    if thinking.is_thinking() {
+        title.push_str(" - the computer is thinking...");
+    }
+    for mut window in windows.iter_mut() {
+        // Only changing the title when it is different keeps the window from being updated
+        // every frame.
+        if window.title != title {
+            window.title = title.clone();
+        }
     }
 }
 
@@ -27,78 +219,118 @@ struct SelectedPiece {
     selected: Option<Entity>,
 }
 
+/// The piece and the square the human player has selected with the mouse for their move.
+#[derive(SystemParam)]
+struct Selection<'w> {
+    mouse_button_inputs: Res<'w, Input<MouseButton>>,
+    square: ResMut<'w, SelectedSquare>,
+    piece: ResMut<'w, SelectedPiece>,
+}
+
 /// Allows the human player to move a piece to an empty square by clicking with the left mouse button
 /// on the piece and desired location. Sends a [`MoveEvent`], which triggers [`update_path`] in controller.rs
+/// Once the game is over, or while a move is being made, taken back or replayed, no moves can be
+/// made. A move that is not allowed in the position is rejected before the [`MoveEvent`] is sent.
 ///
 /// [`update_path`]: super::controller::update_path
 fn perform_move(
-    mouse_button_inputs: Res<Input<MouseButton>>,
-    mut selected_square: ResMut<SelectedSquare>,
-    mut selected_piece: ResMut<SelectedPiece>,
+    mut selection: Selection,
     mut square_query: Query<(&Square, &Interaction)>,
     mut pieces_query: Query<(&mut PieceComponent, Entity)>,
-    mut new_move: EventWriter<MoveEvent>,
-    mut current_move: ResMut<CurrentMove>,
     player_turn: Res<PlayerTurn>,
+    boardstate: Res<BoardState>,
+    state: GameState,
+    mut new_move: NewMove,
 ) {
-    if player_turn.turn == Player::Human {
-        if !mouse_button_inputs.just_pressed(MouseButton::Left) {
+    if player_turn.turn == Player::Human && !state.is_busy() {
+        if !selection
+            .mouse_button_inputs
+            .just_pressed(MouseButton::Left)
+        {
             return;
         }
         //selects the piece that was clicked on
-        if selected_piece.selected.is_none() {
+        if selection.piece.selected.is_none() {
             for (square, interaction) in square_query.iter_mut() {
                 if let Interaction::Clicked = interaction {
-                    let optional_piece = pieces_query.into_iter().find(|piece| {
-                        piece.0.target_x as u8 == square.x && piece.0.target_y as u8 == square.y
-                    });
-                    if optional_piece.is_some() {
-                        if optional_piece
-                            .filter(|piece| piece.0.piece.color == player_turn.color)
-                            .is_some()
-                        {
-                            // Add the identifier of the piece entity to selected_piece. This identifier is later used to query the location of the selected piece.
-                            selected_piece.selected = Some(optional_piece.unwrap().1);
-                            //return so that the selected square won't be the same as the square the selected piece is on.
-                            return;
-                        }
+                    let on_board = boardstate.chess[Pos::new(square.y as isize, square.x as isize)];
+                    // Captured pieces keep pointing to the square they were captured on, so the
+                    // piece that stands there on the board is preferred.
+                    let optional_piece = pieces_query
+                        .iter()
+                        .filter(|piece| {
+                            piece.0.target_x as u8 == square.x && piece.0.target_y as u8 == square.y
+                        })
+                        .max_by_key(|piece| on_board == Some(piece.0.piece));
+                    if let Some((_, entity)) = optional_piece.filter(
+                        |_| matches!(on_board, Some(piece) if piece.color == player_turn.color),
+                    ) {
+                        // Add the identifier of the piece entity to selected_piece. This identifier is later used to query the location of the selected piece.
+                        selection.piece.selected = Some(entity);
+                        //return so that the selected square won't be the same as the square the selected piece is on.
+                        return;
                     }
                 }
             }
         }
         // When a piece is selected, selects a square to where the selected piece will move.
-        if selected_piece.selected.is_some() {
+        if selection.piece.selected.is_some() {
             for (square, interaction) in square_query.iter_mut() {
                 if let Interaction::Clicked = interaction {
-                    selected_square.selected = Some(*square);
+                    selection.square.selected = Some(*square);
                 }
             }
         }
         // Move the selected piece to the selected square.
-        if selected_piece.selected.is_some() && selected_square.selected.is_some() {
+        if let (Some(entity), Some(square)) = (selection.piece.selected, selection.square.selected)
+        {
             // Get the PieceComponent of the piece with the identifier that was specified earlier.
-            let (mut selected_piece_com, _) = pieces_query
-                .get_mut(selected_piece.selected.unwrap())
-                .unwrap();
-
-            *current_move = controller::CurrentMove {
-                current_move: Move {
-                    from: Pos {
-                        x: selected_piece_com.target_y as isize,
-                        y: selected_piece_com.target_x as isize,
-                    },
-                    to: Pos {
-                        x: selected_square.selected.unwrap().y as isize,
-                        y: selected_square.selected.unwrap().x as isize,
-                    },
-                },
+            let (mut selected_piece_com, _) = pieces_query.get_mut(entity).unwrap();
+            let from = Pos {
+                x: selected_piece_com.target_y as isize,
+                y: selected_piece_com.target_x as isize,
+            };
+            let to = Pos {
+                x: square.y as isize,
+                y: square.x as isize,
             };
-            new_move.send(MoveEvent);
-            selected_piece_com.target_x = selected_square.selected.unwrap().x as usize;
-            selected_piece_com.target_y = selected_square.selected.unwrap().y as usize;
+            // The move is looked up in the moves of the position, so whether it is a promotion
+            // follows from the piece on the board. A pawn that reaches the last row is always
+            // promoted to a queen. A move that is not allowed is not made, and the piece has to be
+            // selected again.
+            selection.piece.selected = None;
+            selection.square.selected = None;
+            let m = match boardstate.chess.moves().find(|m| {
+                m.from == from && m.to == to && matches!(m.promotion, None | Some(Kind::Queen))
+            }) {
+                Some(m) => m,
+                None => {
+                    println!("That move is not allowed.");
+                    return;
+                }
+            };
+
+            *new_move.current_move = controller::CurrentMove { current_move: m };
+            new_move.event.send(MoveEvent);
+            // In Chess960 the king castles by moving onto its own rook, but it ends up on the
+            // column of the castling.
+            let castling = boardstate.chess.castling(m);
+            let to = castling.map_or(m.to, |castling| castling.king.to);
+            selected_piece_com.target_x = to.y() as usize;
+            selected_piece_com.target_y = to.x() as usize;
 
-            selected_piece.selected = None;
-            selected_square.selected = None;
+            // When castling, the rook moves along with the king.
+            if let Some(rook_move) = castling.map(|castling| castling.rook) {
+                for (mut piece, other) in pieces_query.iter_mut() {
+                    if other != entity
+                        && piece.target_x as isize == rook_move.from.y()
+                        && piece.target_y as isize == rook_move.from.x()
+                    {
+                        piece.target_x = rook_move.to.y() as usize;
+                        piece.target_y = rook_move.to.x() as usize;
+                    }
+                }
+            }
         }
     }
 }
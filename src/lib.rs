@@ -0,0 +1,8 @@
+/// Handles the boardstate, the rules of chess and the chess computer.
+pub mod chess;
+/// Handles the communication between the simulation, the players and the hardware.
+pub mod controller;
+/// Finds the paths along which the pieces are moved over the board.
+pub mod pathfinding;
+/// Shows the chessboard and the pieces in Bevy.
+pub mod simulation;
@@ -1,11 +1,153 @@
-mod chess;
-mod controller;
-mod pathfinding;
-mod simulation;
-use crate::simulation::app;
+use automated_chessboard::chess::{
+    self,
+    chess::Chess,
+    clock::{Clock, TimeControl},
+    computer::{Difficulty, Minimax, Thinking},
+    engine::UciEngine,
+    pgn::GameRecord,
+    polyglot::OpeningBook,
+    variant::Rules,
+};
+use automated_chessboard::controller::controller::Replay;
+use automated_chessboard::simulation::app;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Runs the whole application.
+/// Runs the whole application. The game starts from the initial position, or from the position
+/// given as a FEN in the first argument. With `--replay <file>` the moves of a PGN file are
+/// replayed on the board, after which the players can continue the game. With
+/// `--chess960 <index|random>` a game of Chess960 starts from the start position with that index,
+/// from 0 up to and including 959, or from a random one. With `--bench` the
+/// speed of the chess computer is measured instead, without opening the simulation.
+/// The strength of the computer player can be set with `--difficulty <easy|medium|hard>` in front
+/// of the other arguments. With `--engine <path>` after that, the moves of the computer player
+/// are played by the UCI engine at the given path instead of the built-in chess computer.
+/// With `--variant <name>` after those, the game is played with the rules of a variant, like
+/// `king-of-the-hill`, `three-check` or `antichess`. With `--clock <time control>` after that,
+/// both players get a clock with the given minutes and increment, like `5+3`, and the computer
+/// thinks as long as its clock allows instead. With `--book <best|random> <file>` after that, the
+/// built-in chess computer plays the moves of a Polyglot opening book while the game is in it.
 fn main() {
-    let mut app = app::create_app(1600.0, 1600.0);
+    let mut args = std::env::args().skip(1).peekable();
+    let difficulty = match args.next_if(|flag| flag == "--difficulty") {
+        Some(_) => args
+            .next()
+            .unwrap_or_else(|| exit_with("Usage: --difficulty <easy|medium|hard>".to_owned()))
+            .parse()
+            .unwrap_or_else(|error| exit_with(format!("Could not set the difficulty: {error}"))),
+        None => Difficulty::default(),
+    };
+    let engine = args.next_if(|flag| flag == "--engine").map(|_| {
+        let path = args
+            .next()
+            .unwrap_or_else(|| exit_with("Usage: --engine <path>".to_owned()));
+        UciEngine::start(&path)
+            .unwrap_or_else(|error| exit_with(format!("Could not start {path}: {error}")))
+    });
+    let rules = args.next_if(|flag| flag == "--variant").map(|_| {
+        args.next()
+            .unwrap_or_else(|| exit_with("Usage: --variant <name>".to_owned()))
+            .parse::<Rules>()
+            .unwrap_or_else(|error| exit_with(format!("Could not set the variant: {error}")))
+    });
+    let clock = args.next_if(|flag| flag == "--clock").map(|_| {
+        let control = args
+            .next()
+            .unwrap_or_else(|| {
+                exit_with("Usage: --clock <minutes>[+<seconds>|d<seconds>]".to_owned())
+            })
+            .parse::<TimeControl>()
+            .unwrap_or_else(|error| exit_with(format!("Could not set the clock: {error}")));
+        Clock::new(control)
+    });
+    let book = args.next_if(|flag| flag == "--book").map(|_| {
+        let usage = || exit_with("Usage: --book <best|random> <file>".to_owned());
+        let choice = args
+            .next()
+            .unwrap_or_else(usage)
+            .parse()
+            .unwrap_or_else(|error| exit_with(format!("Could not use the book: {error}")));
+        let path = args.next().unwrap_or_else(usage);
+        OpeningBook::open(&path, choice)
+            .unwrap_or_else(|error| exit_with(format!("Could not read {path}: {error}")))
+    });
+    let (mut chess, replay) = match args.next() {
+        Some(flag) if flag == "--bench" => {
+            chess::bench::run();
+            return;
+        }
+        Some(flag) if flag == "--replay" => {
+            let path = args
+                .next()
+                .unwrap_or_else(|| exit_with("Usage: --replay <file>".to_owned()));
+            let pgn = std::fs::read_to_string(&path)
+                .unwrap_or_else(|error| exit_with(format!("Could not read {path}: {error}")));
+            let game = GameRecord::from_pgn(&pgn)
+                .unwrap_or_else(|error| exit_with(format!("Could not read {path}: {error}")));
+            let replay = Replay {
+                active: true,
+                moves: game.moves.iter().map(|played| played.m).collect(),
+            };
+            (game.start, Some(replay))
+        }
+        Some(flag) if flag == "--chess960" => {
+            let usage = "Usage: --chess960 <0-959|random>";
+            let index = match args
+                .next()
+                .unwrap_or_else(|| exit_with(usage.to_owned()))
+                .as_str()
+            {
+                "random" => {
+                    let time = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    (time.subsec_nanos() % 960) as u16
+                }
+                index => index
+                    .parse()
+                    .ok()
+                    .filter(|index| *index < 960)
+                    .unwrap_or_else(|| exit_with(usage.to_owned())),
+            };
+            println!("Chess960 start position {index}");
+            (Chess::chess960(index), None)
+        }
+        Some(fen) => {
+            let chess = Chess::from_fen_with_rules(&fen, rules.unwrap_or_default()).unwrap_or_else(
+                |error| exit_with(format!("Could not read the FEN \"{fen}\": {error}")),
+            );
+            (chess, None)
+        }
+        None => (Chess::default(), None),
+    };
+    // A replayed game keeps the variant of its PGN, unless another one is chosen.
+    if let Some(rules) = rules {
+        chess.set_rules(rules);
+    }
+    let mut app = app::create_app(1600.0, 1600.0, chess);
+    app.insert_resource(difficulty);
+    match (engine, book) {
+        (Some(_), Some(_)) => {
+            exit_with("The opening book is only used by the built-in chess computer".to_owned())
+        }
+        (Some(engine), None) => {
+            app.insert_resource(Thinking::new(engine));
+        }
+        (None, Some(book)) => {
+            app.insert_resource(Thinking::new(Minimax::default().with_book(book)));
+        }
+        (None, None) => {}
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(replay);
+    }
+    if let Some(clock) = clock {
+        app.insert_resource(clock);
+    }
     app.run();
 }
+
+/// Prints why the application can not start and stops it.
+fn exit_with(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
@@ -1,12 +1,15 @@
 use crate::{
     chess::{
-        chess::{Chess, Move},
+        chess::{Castling, Chess, Move, Piece},
         pos::Pos,
         BoardState,
     },
-    controller::controller::{CurrentMove, CurrentPaths, NewPathEvent, PathEvent},
+    controller::controller::{
+        CurrentMove, CurrentPaths, NewPathEvent, PathEvent, StartTurnEvent, TakeBack, UndoEvent,
+    },
+    simulation::pieces::PieceComponent,
 };
-use bevy::prelude::{App, EventReader, EventWriter, Plugin, Res, ResMut};
+use bevy::prelude::{App, EventReader, EventWriter, Mut, Plugin, Query, Res, ResMut};
 
 /// Node used for the A* algorithm
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
@@ -30,19 +33,11 @@ struct PathInformation {
     capture: bool,
 }
 ///Vector of positions
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Path {
     pub positions: Vec<Pos>,
 }
 
-impl Default for Path {
-    fn default() -> Self {
-        Self {
-            positions: Default::default(),
-        }
-    }
-}
-
 impl IntoIterator for Path {
     type Item = Pos;
     type IntoIter = <Vec<Pos> as IntoIterator>::IntoIter;
@@ -57,12 +52,14 @@ pub struct PathfindingPlugin;
 
 impl Plugin for PathfindingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(give_path);
+        app.add_system(give_path).add_system(give_undo_path);
     }
 }
 /// When a new [`Pathevent`] is registerd, this function will update [`CurrentPaths`] to
 /// a vector of paths returned by [`calculate_path`]. It then sends a [`NewPathEvent`] which
 /// triggers update_locations in controller.rs
+/// The pieces are passed along, so a piece that is swapped in for a promoted pawn can be pointed
+/// to the promotion square, see [`promotion_paths`].
 ///
 /// [`Pathevent`]: crate::controller::controller::PathEvent
 pub(crate) fn give_path(
@@ -71,22 +68,76 @@ pub(crate) fn give_path(
     boardstate: Res<BoardState>,
     mut current_locations: ResMut<CurrentPaths>,
     mut new_locations: EventWriter<NewPathEvent>,
+    mut pieces_query: Query<&mut PieceComponent>,
 ) {
     for _event in new_move.iter() {
+        let mut pieces: Vec<&mut PieceComponent> =
+            pieces_query.iter_mut().map(Mut::into_inner).collect();
         *current_locations = CurrentPaths {
-            paths: calculate_path(&current_move, &boardstate).unwrap(),
+            paths: calculate_path(&current_move, &boardstate, &mut pieces).unwrap(),
         };
         new_locations.send(NewPathEvent);
     }
 }
 
+/// When a new [`UndoEvent`] is registered, this function will update [`CurrentPaths`] to the paths
+/// returned by [`undo_paths`], which physically take back the last move. Just like [`give_path`],
+/// it then sends a [`NewPathEvent`].
+/// When no paths are found, for example because a piece blocks the way back, the take-back is
+/// skipped: [`TakeBack`] is reset and a [`StartTurnEvent`] is sent, so the game goes on from the
+/// current position.
+///
+/// [`UndoEvent`]: crate::controller::controller::UndoEvent
+pub(crate) fn give_undo_path(
+    mut undo: EventReader<UndoEvent>,
+    boardstate: Res<BoardState>,
+    mut current_locations: ResMut<CurrentPaths>,
+    mut new_locations: EventWriter<NewPathEvent>,
+    mut take_back: ResMut<TakeBack>,
+    mut start_turn: EventWriter<StartTurnEvent>,
+) {
+    for _event in undo.iter() {
+        match undo_paths(&boardstate) {
+            Some(paths) => {
+                *current_locations = CurrentPaths { paths };
+                new_locations.send(NewPathEvent);
+            }
+            None => {
+                println!("The move cannot be taken back, there is no free path on the board.");
+                take_back.moves = 0;
+                start_turn.send(StartTurnEvent { moved: false });
+            }
+        }
+    }
+}
+
 /// Calculates all the paths that are necessary for a move to occur without any collisions.
 /// Any obstructing pieces will first move out of the way of a captured piece that is moving to the graveyard.
 /// After this piece has reached the graveyard, any obstructing pieces will move out of the way of the attacking piece,
 /// When the attacking piece has reached its destination. The the pieces that moved out of the attacking piece's way
 /// will return to their original positions. Then pieces that moved out of the captured piece's way will return
 /// to their original positions.
-fn calculate_path(mov: &Res<CurrentMove>, boardstate: &Res<BoardState>) -> Option<Vec<Path>> {
+fn calculate_path(
+    mov: &CurrentMove,
+    boardstate: &BoardState,
+    pieces: &mut [&mut PieceComponent],
+) -> Option<Vec<Path>> {
+    // A promotion is performed as the pawn's move, followed by swapping the pawn for the
+    // promoted piece.
+    if mov.current_move.promotion.is_some() {
+        let pawn_move = CurrentMove {
+            current_move: Move {
+                promotion: None,
+                ..mov.current_move
+            },
+        };
+        let mut paths = calculate_path(&pawn_move, boardstate, &mut [])?;
+        paths.extend(promotion_paths(mov.current_move, boardstate, pieces)?);
+        return Some(paths);
+    }
+    if let Some(castling) = boardstate.chess.castling(mov.current_move) {
+        return castling_paths(castling, boardstate);
+    }
     let mut paths_info: Vec<PathInformation> = vec![];
     // The path for the original move as received by the controller.
     let original_path_info = a_star(mov.current_move.from, mov.current_move.to, boardstate)?;
@@ -94,8 +145,10 @@ fn calculate_path(mov: &Res<CurrentMove>, boardstate: &Res<BoardState>) -> Optio
     let mut capture_path_info: PathInformation = original_path_info.clone();
     paths_info.push(original_path_info.clone());
     // If a piece has been captured, calculate a path to the graveyard for this piece.
-    if original_path_info.capture {
-        capture_path_info = capture(mov.current_move.to, boardstate)?;
+    // This piece is not always on the destination of the move: a pawn captured en passant
+    // stands next to it.
+    if let Some(captured) = boardstate.chess.captured_pos(mov.current_move) {
+        capture_path_info = capture(captured, boardstate)?;
         // If no pieces have been crossed in the original path,
         // the captured piece should move first and thus be the first element
         // in the paths vector.
@@ -209,10 +262,109 @@ fn calculate_path(mov: &Res<CurrentMove>, boardstate: &Res<BoardState>) -> Optio
     )
 }
 
+/// Calculates the paths for a castling move, for the steps of [`Chess::castling_steps`]. Usually
+/// the king moves first, over the squares that are empty by the rules of castling. The rook then
+/// looks for a path on the board with the king already on its new position, which takes it around
+/// the king over the border of the board.
+fn castling_paths(castling: Castling, boardstate: &BoardState) -> Option<Vec<Path>> {
+    let mut during_castling = BoardState {
+        chess: boardstate.chess.clone(),
+    };
+    boardstate
+        .chess
+        .castling_steps(castling)
+        .into_iter()
+        .map(|m| {
+            let path_info = a_star(m.from, m.to, &during_castling)?;
+            during_castling.chess.move_piece(m.from, m.to);
+            Some(path_info.path)
+        })
+        .collect()
+}
+
+/// Calculates the paths that take back the last move, see [`Chess::undo_moves`]. The pieces move
+/// one after the other, each looking for a path on the board as the pieces before it left it.
+fn undo_paths(boardstate: &BoardState) -> Option<Vec<Path>> {
+    let mut during_undo = BoardState {
+        chess: boardstate.chess.clone(),
+    };
+    boardstate
+        .chess
+        .undo_moves()
+        .into_iter()
+        .map(|m| {
+            let path_info = a_star(m.from, m.to, &during_undo)?;
+            during_undo.chess.move_piece(m.from, m.to);
+            Some(path_info.path)
+        })
+        .collect()
+}
+
+/// Calculates the paths that swap a promoted pawn, which has already reached the last row, for a
+/// captured piece of the promoted kind. The pawn moves to its graveyard first, after which the
+/// spare piece moves from the graveyard onto the promotion square.
+/// When no such piece has been captured yet there is nothing to swap; the pawn stays on the
+/// board and takes the role of the promoted piece.
+/// The [`PieceComponent`] of the spare piece is pointed to the promotion square, so it can be
+/// selected there. Captured pieces keep pointing to the square they were captured on, so the
+/// spare piece is one that does not stand on the square it points to, and the pawn takes over
+/// that square instead.
+fn promotion_paths(
+    m: Move,
+    boardstate: &BoardState,
+    pieces: &mut [&mut PieceComponent],
+) -> Option<Vec<Path>> {
+    let kind = m.promotion?;
+    let pawn = boardstate.chess[m.from]?;
+    let promoted = Piece {
+        color: pawn.color,
+        kind,
+    };
+    let mut after_move = BoardState {
+        chess: boardstate.chess.clone(),
+    };
+    after_move.chess.perform(Move {
+        promotion: None,
+        ..m
+    });
+    match after_move.chess.promotion_swap(m.to, promoted) {
+        Some((pawn_move, spare_move)) => {
+            let pawn_path_info = a_star(pawn_move.from, pawn_move.to, &after_move)?;
+            after_move.chess.move_piece(pawn_move.from, pawn_move.to);
+            let spare_path_info = a_star(spare_move.from, spare_move.to, &after_move)?;
+
+            let target =
+                |piece: &PieceComponent| Pos::new(piece.target_y as isize, piece.target_x as isize);
+            let spare_target = pieces
+                .iter_mut()
+                .find(|piece| {
+                    piece.piece == promoted && after_move.chess[target(piece)] != Some(promoted)
+                })
+                .map(|spare| {
+                    let captured_on = (spare.target_x, spare.target_y);
+                    spare.target_x = m.to.y() as usize;
+                    spare.target_y = m.to.x() as usize;
+                    captured_on
+                });
+            let promoted_pawn = pieces
+                .iter_mut()
+                .find(|piece| piece.piece == pawn && target(piece) == m.to);
+            if let (Some((target_x, target_y)), Some(promoted_pawn)) = (spare_target, promoted_pawn)
+            {
+                promoted_pawn.target_x = target_x;
+                promoted_pawn.target_y = target_y;
+            }
+            Some(vec![pawn_path_info.path, spare_path_info.path])
+        }
+        // The controller lets the human player know, see `end_turn`.
+        None => Some(vec![]),
+    }
+}
+
 /// Finds the shortest path using the a* algorithm on the board between a start and end position,
 /// based on the current boardstate.
 /// The function returns a path, and information about captured and crossed pieces on that path.
-fn a_star(start_pos: Pos, end_pos: Pos, boardstate: &Res<BoardState>) -> Option<PathInformation> {
+fn a_star(start_pos: Pos, end_pos: Pos, boardstate: &BoardState) -> Option<PathInformation> {
     let start_node: Node = Node {
         pos: start_pos,
         distance_to_start: 0,
@@ -322,21 +474,14 @@ fn a_star(start_pos: Pos, end_pos: Pos, boardstate: &Res<BoardState>) -> Option<
 
 /// Checks whether a given position is on the board.
 fn within_bounds(row: isize, col: isize) -> bool {
-    (row >= -3 && row <= 10) && (col >= -1 && col <= 8)
+    (-3..=10).contains(&row) && (-1..=8).contains(&col)
 }
 
 ///Finds a path to the graveyard for a captured piece.
-fn capture(start_pos: Pos, boardstate: &Res<BoardState>) -> Option<PathInformation> {
-    let end_pos = boardstate
-        .chess
-        .graveyard_positions()
-        .filter(|pos| boardstate.chess[pos].is_none())
-        .min_by(|a, b| {
-            a.distance(start_pos)
-                .partial_cmp(&b.distance(start_pos))
-                .unwrap()
-        })
-        .unwrap();
+/// The captured piece goes to the same graveyard position [`Chess::perform`] puts it in.
+fn capture(start_pos: Pos, boardstate: &BoardState) -> Option<PathInformation> {
+    let piece = boardstate.chess[start_pos]?;
+    let end_pos = boardstate.chess.graveyard_slot(piece, start_pos)?;
     a_star(start_pos, end_pos, boardstate)
 }
 
@@ -347,9 +492,9 @@ fn capture(start_pos: Pos, boardstate: &Res<BoardState>) -> Option<PathInformati
 /// 4) is closest to the start position of the obstructing piece
 fn find_end_pos(
     start_pos: Pos,
-    paths: &Vec<PathInformation>,
+    paths: &[PathInformation],
     boardstate: &BoardState,
-    locations: &Vec<Move>,
+    locations: &[Move],
 ) -> Move {
     let end_pos = Chess::board_positions()
         .filter(|pos| {
@@ -374,10 +519,7 @@ fn find_end_pos(
                 .unwrap()
         }) // 4) lowest value for .distance()
         .unwrap();
-    Move {
-        from: start_pos,
-        to: end_pos,
-    }
+    Move::new(start_pos, end_pos)
 }
 
 //TESTS
@@ -385,7 +527,7 @@ fn find_end_pos(
 mod tests {
     use super::*;
     use crate::chess::{
-        chess::{Color, Piece},
+        chess::{Color, Kind},
         BoardState,
     };
 
@@ -402,10 +544,11 @@ mod tests {
     fn test_find_end_pos() {
         // Create a board state with some pieces
         let mut board_state = BoardState::default();
-        board_state.chess.board[2][2] = Some(Piece::WHITE_ROOK);
-        board_state.chess.board[3][2] = Some(Piece::BLACK_PAWN);
-        board_state.chess.board[2][3] = Some(Piece::BLACK_KNIGHT);
-        board_state.chess.turn = Color::White;
+        let chess = &mut board_state.chess;
+        chess.set(Pos::new(2, 2), Some(Piece::WHITE_ROOK));
+        chess.set(Pos::new(2, 3), Some(Piece::BLACK_PAWN));
+        chess.set(Pos::new(3, 2), Some(Piece::BLACK_KNIGHT));
+        chess.turn = Color::White;
 
         // Define the start position, paths, and occupied locations
         let start_pos = Pos::new(2, 2);
@@ -426,14 +569,8 @@ mod tests {
             },
         ];
         let locations = vec![
-            Move {
-                from: Pos::new(2, 2),
-                to: Pos::new(3, 2),
-            },
-            Move {
-                from: Pos::new(2, 2),
-                to: Pos::new(2, 3),
-            },
+            Move::new(Pos::new(2, 2), Pos::new(3, 2)),
+            Move::new(Pos::new(2, 2), Pos::new(2, 3)),
         ];
 
         // Call find_end_pos function
@@ -448,4 +585,162 @@ mod tests {
         assert!(!locations.iter().any(|loc| loc.to == end_pos.to));
         assert!(board_state.chess[end_pos.to].is_none());
     }
+
+    #[test]
+    fn test_en_passant_paths() {
+        let mut board_state = BoardState::default();
+        let chess = &mut board_state.chess;
+        // A white pawn on e5 next to a black pawn that just leaped to d5.
+        chess.set(Pos::new(4, 1), None);
+        chess.set(Pos::new(4, 4), Some(Piece::WHITE_PAWN));
+        chess.set(Pos::new(3, 6), None);
+        chess.set(Pos::new(3, 4), Some(Piece::BLACK_PAWN));
+        chess.en_passant = Some(Pos::new(3, 5));
+        let current_move = CurrentMove {
+            current_move: Move::new(Pos::new(4, 4), Pos::new(3, 5)),
+        };
+
+        let paths = calculate_path(&current_move, &board_state, &mut []).unwrap();
+
+        // The captured pawn leaves for the graveyard from d5 before the white pawn moves.
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].positions.first(), Some(&Pos::new(3, 4)));
+        assert_eq!(paths[0].positions.last().unwrap().x(), -2);
+        assert_eq!(paths[1].positions.first(), Some(&Pos::new(4, 4)));
+        assert_eq!(paths[1].positions.last(), Some(&Pos::new(3, 5)));
+    }
+
+    #[test]
+    fn test_capture_to_full_column() {
+        let mut board_state = BoardState::default();
+        let chess = &mut board_state.chess;
+        // The column of the black graveyard next to the board is full.
+        for pos in Chess::graveyard_of(Color::Black).take(10) {
+            chess.set(pos, Some(Piece::BLACK_PAWN));
+        }
+        chess.set(Pos::new(3, 6), None);
+        chess.set(Pos::new(3, 2), Some(Piece::BLACK_PAWN));
+        let current_move = CurrentMove {
+            current_move: Move::new(Pos::new(4, 1), Pos::new(3, 2)),
+        };
+
+        let paths = calculate_path(&current_move, &board_state, &mut []).unwrap();
+
+        // The captured pawn crosses the full column to get to the outer one.
+        let capture = paths
+            .iter()
+            .find(|path| path.positions.first() == Some(&Pos::new(3, 2)))
+            .unwrap();
+        assert_eq!(capture.positions.last().unwrap().x(), -3);
+    }
+
+    #[test]
+    fn test_castling_paths() {
+        let mut board_state = BoardState::default();
+        // Clear the squares between the white king and its king side rook.
+        board_state.chess.set(Pos::new(5, 0), None);
+        board_state.chess.set(Pos::new(6, 0), None);
+        let king_move = Move::new(Pos::new(4, 0), Pos::new(6, 0));
+        let castling = board_state.chess.castling(king_move).unwrap();
+
+        let paths = castling_paths(castling, &board_state).unwrap();
+
+        // The king moves first, the rook follows without passing the king's new square.
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].positions.first(), Some(&Pos::new(4, 0)));
+        assert_eq!(paths[0].positions.last(), Some(&Pos::new(6, 0)));
+        assert_eq!(paths[1].positions.first(), Some(&Pos::new(7, 0)));
+        assert_eq!(paths[1].positions.last(), Some(&Pos::new(5, 0)));
+        assert!(!paths[1].positions.contains(&Pos::new(6, 0)));
+    }
+
+    #[test]
+    fn test_promotion_paths() {
+        let mut board_state = BoardState::default();
+        let chess = &mut board_state.chess;
+        // A white pawn on a7 about to promote on the empty a8, with a captured queen available.
+        chess.set(Pos::new(0, 6), Some(Piece::WHITE_PAWN));
+        chess.set(Pos::new(0, 7), None);
+        chess.set(Pos::new(9, 3), Some(Piece::WHITE_QUEEN));
+        let promotion = Move {
+            promotion: Some(Kind::Queen),
+            ..Move::new(Pos::new(0, 6), Pos::new(0, 7))
+        };
+
+        // The pawn was pointed to a8 when it moved there, and the spare queen still points to
+        // c6, where it was captured.
+        let mut pawn = PieceComponent {
+            piece: Piece::WHITE_PAWN,
+            target_x: 7,
+            target_y: 0,
+        };
+        let mut queen = PieceComponent {
+            piece: Piece::WHITE_QUEEN,
+            target_x: 0,
+            target_y: 3,
+        };
+        let mut spare = PieceComponent {
+            piece: Piece::WHITE_QUEEN,
+            target_x: 5,
+            target_y: 2,
+        };
+
+        let paths = promotion_paths(
+            promotion,
+            &board_state,
+            &mut [&mut pawn, &mut queen, &mut spare],
+        )
+        .unwrap();
+
+        // The pawn leaves for the graveyard and the queen comes out of it.
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].positions.first(), Some(&Pos::new(0, 7)));
+        assert_eq!(paths[0].positions.last().unwrap().x(), 9);
+        assert_eq!(paths[1].positions.first(), Some(&Pos::new(9, 3)));
+        assert_eq!(paths[1].positions.last(), Some(&Pos::new(0, 7)));
+        // The spare queen can be selected on a8, the queen on d1 stays where it is.
+        assert_eq!((spare.target_x, spare.target_y), (7, 0));
+        assert_eq!((pawn.target_x, pawn.target_y), (5, 2));
+        assert_eq!((queen.target_x, queen.target_y), (0, 3));
+
+        // Without a captured knight there is nothing to swap.
+        let underpromotion = Move {
+            promotion: Some(Kind::Knight),
+            ..promotion
+        };
+        assert_eq!(
+            promotion_paths(underpromotion, &board_state, &mut []),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_undo_paths() {
+        let mut board_state = BoardState::default();
+        // White's e-pawn captures the black pawn on d5.
+        for m in [
+            Move::new(Pos::new(4, 1), Pos::new(4, 3)),
+            Move::new(Pos::new(3, 6), Pos::new(3, 4)),
+            Move::new(Pos::new(4, 3), Pos::new(3, 4)),
+        ] {
+            board_state.chess.perform(m);
+        }
+        let slot = board_state
+            .chess
+            .history
+            .last()
+            .unwrap()
+            .capture
+            .unwrap()
+            .slot;
+
+        let paths = undo_paths(&board_state).unwrap();
+
+        // The pawn moves back first, after which the captured pawn returns from the graveyard.
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].positions.first(), Some(&Pos::new(3, 4)));
+        assert_eq!(paths[0].positions.last(), Some(&Pos::new(4, 3)));
+        assert_eq!(paths[1].positions.first(), slot.as_ref());
+        assert_eq!(paths[1].positions.last(), Some(&Pos::new(3, 4)));
+    }
 }
@@ -6,8 +6,11 @@ use bevy_rapier3d::{
 };
 
 use crate::{
-    chess::{computer::ChessComputerPlugin, *},
-    controller::{controller::ControllerPlugin, ui::UserInterfacePlugin},
+    chess::{chess::Chess, computer::ChessComputerPlugin, pgn::GameRecord, *},
+    controller::{
+        controller::{ControllerPlugin, Player, PlayerTurn},
+        ui::UserInterfacePlugin,
+    },
     pathfinding::astar::PathfindingPlugin,
     simulation::board::*,
     simulation::camera::{self, CameraPlugin},
@@ -17,16 +20,35 @@ use crate::{
 
 use super::magnet::MagnetPlugin;
 
+/// The title of the window.
+pub const TITLE: &str = "Automatic chessboard simulation";
+
 /// Creates the app using all the plugins from other components.
-/// Shows the simulation in a screen of the given size.
-pub fn create_app(screen_width: f32, screen_height: f32) -> App {
+/// Shows the simulation in a screen of the given size, starting from the given position.
+pub fn create_app(screen_width: f32, screen_height: f32, chess: Chess) -> App {
     let resolution = WindowResolution::new(screen_width, screen_height);
+    let color = PlayerTurn::default().color;
+    // The computer makes the first move when the position starts with its turn.
+    let turn = if chess.turn == color {
+        Player::Human
+    } else {
+        Player::Computer
+    };
+    let (white, black) = match color {
+        chess::Color::White => ("Human", "Computer"),
+        chess::Color::Black => ("Computer", "Human"),
+    };
+    let history = GameHistory {
+        game: GameRecord::new(chess.clone(), white, black),
+    };
     let mut app = App::new();
-    app.insert_resource(BoardState::default())
+    app.insert_resource(BoardState { chess })
+        .insert_resource(history)
+        .insert_resource(PlayerTurn { color, turn })
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 resolution: resolution.clone(),
-                title: "Automatic chessboard simulation".to_owned(),
+                title: TITLE.to_owned(),
                 ..default()
             }),
             ..default()
@@ -316,7 +316,7 @@ fn create_pieces(
 
     for (position, piece) in state
         .chess
-        .board
+        .board()
         .iter()
         .enumerate()
         .flat_map(|(row, pieces)| {